@@ -1,20 +1,65 @@
 use std::collections::HashMap;
 
+use clap::ValueEnum;
 use nbt::{Blob, Value};
+use serde::Deserialize;
 use strsim::normalized_levenshtein;
 
+/// ThaumCraft version the player data comes from, deciding the aspect set and recipes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edition {
+    /// ThaumCraft 4 for MineCraft 1.7.10, reading `.thaum` files
+    #[default]
+    Tc4,
+    /// ThaumCraft 6 for MineCraft 1.12, reading the knowledge capability of `<uuid>.dat` files
+    Tc6,
+}
+
+impl Edition {
+    pub fn name(self) -> &'static str {
+        match self {
+            Edition::Tc4 => "ThaumCraft 4",
+            Edition::Tc6 => "ThaumCraft 6",
+        }
+    }
+
+    /// The aspects that exist in this edition, in the order of `Aspect::values`.
+    pub fn aspects(self) -> Vec<Aspect> {
+        Aspect::values().iter().copied().filter(|&aspect| self.contains(aspect)).collect()
+    }
+
+    pub fn contains(self, aspect: Aspect) -> bool {
+        match self {
+            Edition::Tc4 => !matches!(aspect, Aspect::Alkimia | Aspect::Aversio | Aspect::Desiderium | Aspect::Praemunio),
+            Edition::Tc6 => aspect.is_primal() || aspect.tc6_components().is_some(),
+        }
+    }
+
+    /// Rejects aspects of the other edition, which no path of this one can use.
+    pub fn check(self, aspect: Aspect) -> Result<Aspect, String> {
+        match self.contains(aspect) {
+            true => Ok(aspect),
+            false => Err(format!("{:?} is not an aspect of {}", aspect, self.name())),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Aspect {
     Aer,
     Alienis,
+    Alkimia,
     Aqua,
     Arbor,
     Auram,
+    Aversio,
     Bestia,
     Caelum,
     Cognitio,
     Corpus,
     Desidia,
+    Desiderium,
     Electrum,
     Exanimis,
     Fabrico,
@@ -49,6 +94,7 @@ pub enum Aspect {
     Permutatio,
     Potentia,
     Praecantatio,
+    Praemunio,
     Primordium,
     Radio,
     Sano,
@@ -73,18 +119,21 @@ pub enum Aspect {
 }
 
 impl Aspect {
-    fn values() -> &'static [Aspect] {
-        static VALUES: [Aspect; 65] = [
+    pub fn values() -> &'static [Aspect] {
+        static VALUES: [Aspect; 69] = [
             Aspect::Aer,
             Aspect::Alienis,
+            Aspect::Alkimia,
             Aspect::Aqua,
             Aspect::Arbor,
             Aspect::Auram,
+            Aspect::Aversio,
             Aspect::Bestia,
             Aspect::Caelum,
             Aspect::Cognitio,
             Aspect::Corpus,
             Aspect::Desidia,
+            Aspect::Desiderium,
             Aspect::Electrum,
             Aspect::Exanimis,
             Aspect::Fabrico,
@@ -119,6 +168,7 @@ impl Aspect {
             Aspect::Permutatio,
             Aspect::Potentia,
             Aspect::Praecantatio,
+            Aspect::Praemunio,
             Aspect::Primordium,
             Aspect::Radio,
             Aspect::Sano,
@@ -156,49 +206,217 @@ impl Aspect {
         }
     }
 
-    pub fn get_by_key(name: &String) -> Option<Aspect> {
+    pub fn is_primal(&self) -> bool {
+        matches!(self, Aspect::Aer | Aspect::Aqua | Aspect::Ignis | Aspect::Ordo | Aspect::Perditio | Aspect::Terra)
+    }
+
+    /// The two aspects combined into this one in the given edition, or `None` for primal, custom
+    /// and aspects missing from that edition.
+    pub fn components(&self, edition: Edition) -> Option<(Aspect, Aspect)> {
+        match edition {
+            Edition::Tc4 => self.tc4_components(),
+            Edition::Tc6 => self.tc6_components(),
+        }
+    }
+
+    fn tc4_components(&self) -> Option<(Aspect, Aspect)> {
+        match self {
+            Aspect::Alienis => Some((Aspect::Vacuos, Aspect::Tenebrae)),
+            Aspect::Arbor => Some((Aspect::Aer, Aspect::Herba)),
+            Aspect::Auram => Some((Aspect::Praecantatio, Aspect::Aer)),
+            Aspect::Bestia => Some((Aspect::Motus, Aspect::Victus)),
+            Aspect::Caelum => Some((Aspect::Vitreus, Aspect::Metallum)),
+            Aspect::Cognitio => Some((Aspect::Ignis, Aspect::Spiritus)),
+            Aspect::Corpus => Some((Aspect::Mortuus, Aspect::Bestia)),
+            Aspect::Desidia => Some((Aspect::Vinculum, Aspect::Spiritus)),
+            Aspect::Electrum => Some((Aspect::Potentia, Aspect::Machina)),
+            Aspect::Exanimis => Some((Aspect::Motus, Aspect::Mortuus)),
+            Aspect::Fabrico => Some((Aspect::Humanus, Aspect::Instrumentum)),
+            Aspect::Fames => Some((Aspect::Victus, Aspect::Vacuos)),
+            Aspect::Gelum => Some((Aspect::Ignis, Aspect::Perditio)),
+            Aspect::Gula => Some((Aspect::Fames, Aspect::Vacuos)),
+            Aspect::Herba => Some((Aspect::Victus, Aspect::Terra)),
+            Aspect::Humanus => Some((Aspect::Bestia, Aspect::Cognitio)),
+            Aspect::Infernus => Some((Aspect::Ignis, Aspect::Praecantatio)),
+            Aspect::Instrumentum => Some((Aspect::Humanus, Aspect::Ordo)),
+            Aspect::Invidia => Some((Aspect::Sensus, Aspect::Fames)),
+            Aspect::Ira => Some((Aspect::Telum, Aspect::Ignis)),
+            Aspect::Iter => Some((Aspect::Motus, Aspect::Terra)),
+            Aspect::Limus => Some((Aspect::Victus, Aspect::Aqua)),
+            Aspect::Lucrum => Some((Aspect::Humanus, Aspect::Fames)),
+            Aspect::Lux => Some((Aspect::Aer, Aspect::Ignis)),
+            Aspect::Luxuria => Some((Aspect::Corpus, Aspect::Fames)),
+            Aspect::Machina => Some((Aspect::Motus, Aspect::Instrumentum)),
+            Aspect::Magneto => Some((Aspect::Metallum, Aspect::Iter)),
+            Aspect::Messis => Some((Aspect::Herba, Aspect::Humanus)),
+            Aspect::Metallum => Some((Aspect::Terra, Aspect::Vitreus)),
+            Aspect::Meto => Some((Aspect::Messis, Aspect::Instrumentum)),
+            Aspect::Mortuus => Some((Aspect::Victus, Aspect::Perditio)),
+            Aspect::Motus => Some((Aspect::Aer, Aspect::Ordo)),
+            Aspect::Nebrisum => Some((Aspect::Perfodio, Aspect::Lucrum)),
+            Aspect::Pannus => Some((Aspect::Instrumentum, Aspect::Bestia)),
+            Aspect::Perfodio => Some((Aspect::Humanus, Aspect::Terra)),
+            Aspect::Permutatio => Some((Aspect::Perditio, Aspect::Ordo)),
+            Aspect::Potentia => Some((Aspect::Ordo, Aspect::Ignis)),
+            Aspect::Praecantatio => Some((Aspect::Vacuos, Aspect::Potentia)),
+            Aspect::Radio => Some((Aspect::Lux, Aspect::Potentia)),
+            Aspect::Sano => Some((Aspect::Victus, Aspect::Ordo)),
+            Aspect::Sensus => Some((Aspect::Aer, Aspect::Spiritus)),
+            Aspect::Spiritus => Some((Aspect::Victus, Aspect::Mortuus)),
+            Aspect::Strontio => Some((Aspect::Cognitio, Aspect::Perditio)),
+            Aspect::Superbia => Some((Aspect::Volatus, Aspect::Vacuos)),
+            Aspect::Tabernus => Some((Aspect::Tutamen, Aspect::Iter)),
+            Aspect::Telum => Some((Aspect::Instrumentum, Aspect::Ignis)),
+            Aspect::Tempestas => Some((Aspect::Aer, Aspect::Aqua)),
+            Aspect::Tempus => Some((Aspect::Vacuos, Aspect::Ordo)),
+            Aspect::Tenebrae => Some((Aspect::Vacuos, Aspect::Lux)),
+            Aspect::Tutamen => Some((Aspect::Instrumentum, Aspect::Terra)),
+            Aspect::Vacuos => Some((Aspect::Aer, Aspect::Perditio)),
+            Aspect::Venenum => Some((Aspect::Aqua, Aspect::Perditio)),
+            Aspect::Victus => Some((Aspect::Aqua, Aspect::Terra)),
+            Aspect::Vinculum => Some((Aspect::Motus, Aspect::Perditio)),
+            Aspect::Vitium => Some((Aspect::Praecantatio, Aspect::Perditio)),
+            Aspect::Vitreus => Some((Aspect::Terra, Aspect::Ordo)),
+            Aspect::Volatus => Some((Aspect::Aer, Aspect::Motus)),
+            _ => None,
+        }
+    }
+
+    /// ThaumCraft 6 dropped many aspects, added four and changed some recipes, e.g. Metallum is Terra + Ordo.
+    fn tc6_components(&self) -> Option<(Aspect, Aspect)> {
+        match self {
+            Aspect::Alienis => Some((Aspect::Vacuos, Aspect::Tenebrae)),
+            Aspect::Alkimia => Some((Aspect::Praecantatio, Aspect::Aqua)),
+            Aspect::Auram => Some((Aspect::Praecantatio, Aspect::Aer)),
+            Aspect::Aversio => Some((Aspect::Spiritus, Aspect::Perditio)),
+            Aspect::Bestia => Some((Aspect::Motus, Aspect::Victus)),
+            Aspect::Cognitio => Some((Aspect::Ignis, Aspect::Spiritus)),
+            Aspect::Desiderium => Some((Aspect::Spiritus, Aspect::Vacuos)),
+            Aspect::Exanimis => Some((Aspect::Motus, Aspect::Mortuus)),
+            Aspect::Fabrico => Some((Aspect::Permutatio, Aspect::Instrumentum)),
+            Aspect::Gelum => Some((Aspect::Ignis, Aspect::Perditio)),
+            Aspect::Herba => Some((Aspect::Victus, Aspect::Terra)),
+            Aspect::Humanus => Some((Aspect::Spiritus, Aspect::Victus)),
+            Aspect::Instrumentum => Some((Aspect::Metallum, Aspect::Potentia)),
+            Aspect::Lux => Some((Aspect::Aer, Aspect::Ignis)),
+            Aspect::Machina => Some((Aspect::Motus, Aspect::Instrumentum)),
+            Aspect::Metallum => Some((Aspect::Terra, Aspect::Ordo)),
+            Aspect::Mortuus => Some((Aspect::Aqua, Aspect::Perditio)),
+            Aspect::Motus => Some((Aspect::Aer, Aspect::Ordo)),
+            Aspect::Permutatio => Some((Aspect::Perditio, Aspect::Ordo)),
+            Aspect::Potentia => Some((Aspect::Ordo, Aspect::Ignis)),
+            Aspect::Praecantatio => Some((Aspect::Vacuos, Aspect::Potentia)),
+            Aspect::Praemunio => Some((Aspect::Spiritus, Aspect::Terra)),
+            Aspect::Sensus => Some((Aspect::Aer, Aspect::Spiritus)),
+            Aspect::Spiritus => Some((Aspect::Victus, Aspect::Mortuus)),
+            Aspect::Tenebrae => Some((Aspect::Vacuos, Aspect::Lux)),
+            Aspect::Vacuos => Some((Aspect::Aer, Aspect::Perditio)),
+            Aspect::Victus => Some((Aspect::Aqua, Aspect::Terra)),
+            Aspect::Vinculum => Some((Aspect::Motus, Aspect::Perditio)),
+            Aspect::Vitium => Some((Aspect::Perditio, Aspect::Praecantatio)),
+            Aspect::Vitreus => Some((Aspect::Terra, Aspect::Aer)),
+            Aspect::Volatus => Some((Aspect::Aer, Aspect::Motus)),
+            _ => None,
+        }
+    }
+
+    pub fn get_by_key(name: &str) -> Option<Aspect> {
         for variant in Aspect::values().iter() {
             if variant.key().eq_ignore_ascii_case(name) {
-                return Some(variant.clone());
+                return Some(*variant);
             }
         }
 
         None
     }
 
-    pub fn from_str_fuzzy(name: &String) -> Option<(Aspect, f64)> {
+    /// Closest aspect of the edition to the name, with a similarity of 1.0 for an exact match.
+    pub fn from_str_fuzzy(name: &str, edition: Edition) -> Option<(Aspect, f64)> {
         let mut highest_score = 0.0;
         let mut best_match = None;
 
-        for variant in Aspect::values().iter() {
+        for variant in edition.aspects() {
             let variant_name = variant.display_name();
             let input_name = name.to_lowercase();
             let score = normalized_levenshtein(&variant_name, &input_name);
 
             if score > highest_score {
                 highest_score = score;
-                best_match = Some(variant.clone());
+                best_match = Some(variant);
             }
         }
-        if best_match.is_some() {
-            Some((best_match.unwrap(), highest_score))
-        } else {
-            None
-        }
+        best_match.map(|aspect| (aspect, highest_score))
     }
 }
 
+/// Amounts of the aspects a player has discovered. Undiscovered aspects have no entry at all,
+/// while discovered aspects keep their entry even when the amount drops to zero.
 #[derive(Debug, Clone)]
 pub struct AspectInventory {
     inventory: HashMap<Aspect, u16>,
     max_amount: u16,
+    edition: Edition,
 }
 
 impl AspectInventory {
+    pub fn new(inventory: HashMap<Aspect, u16>, edition: Edition) -> Self {
+        let max_amount = inventory.values().cloned().max().unwrap_or_default();
+        AspectInventory { inventory, max_amount, edition }
+    }
+
+    /// Every aspect of the edition discovered with the same amount, for games without a per-player aspect pool.
+    pub fn uniform(edition: Edition) -> Self {
+        AspectInventory::new(edition.aspects().into_iter().map(|aspect| (aspect, 1)).collect(), edition)
+    }
+
+    pub fn pooled<'a>(inventories: impl IntoIterator<Item = &'a AspectInventory>) -> Self {
+        let mut inventory: HashMap<Aspect, u16> = HashMap::new();
+        let mut edition = Edition::default();
+        for other in inventories {
+            edition = other.edition;
+            for (&aspect, &amount) in &other.inventory {
+                let pooled_amount = inventory.entry(aspect).or_default();
+                *pooled_amount = pooled_amount.saturating_add(amount);
+            }
+        }
+
+        AspectInventory::new(inventory, edition)
+    }
+
+    pub fn edition(&self) -> Edition {
+        self.edition
+    }
+
+    pub fn max_amount(&self) -> u16 {
+        self.max_amount
+    }
+
+    /// Same amounts priced against a larger maximum, so that prices of several inventories share one scale.
+    /// Never goes below the largest amount, whatever maximum this inventory was priced against before.
+    pub fn with_max_amount(&self, max_amount: u16) -> AspectInventory {
+        AspectInventory {
+            inventory: self.inventory.clone(),
+            max_amount: max_amount.max(self.inventory.values().copied().max().unwrap_or_default()),
+            edition: self.edition,
+        }
+    }
+
     pub fn amount_of(&self, aspect: Aspect) -> u16 {
         self.inventory.get(&aspect).copied().unwrap_or(0)
     }
 
+    pub fn is_discovered(&self, aspect: Aspect) -> bool {
+        self.inventory.contains_key(&aspect)
+    }
+
+    /// Amount of the aspect for reports, with `-` marking undiscovered aspects.
+    pub fn display_amount(&self, aspect: Aspect) -> String {
+        match self.inventory.get(&aspect) {
+            Some(amount) => amount.to_string(),
+            None => "-".to_string(),
+        }
+    }
+
     pub fn from_nbt(nbt: Blob) -> Result<AspectInventory, String> {
         let aspect_values = match nbt.get("THAUMCRAFT.ASPECTS") {
             Some(nbt::Value::List(aspects)) => aspects,
@@ -211,9 +429,7 @@ impl AspectInventory {
             inventory.insert(aspect, amount);
         }
 
-        let max_amount = inventory.values().cloned().max().unwrap_or_default();
-
-        Ok(AspectInventory { inventory, max_amount })
+        Ok(AspectInventory::new(inventory, Edition::Tc4))
     }
 
     pub fn price_of(&self, aspect: Aspect) -> u16 {
@@ -245,7 +461,7 @@ impl AspectInventory {
                 .try_into()
                 .map_err(|_| "Aspect amount is negative".to_string())?;
 
-            if let Some(aspect) = Aspect::get_by_key(&aspect_key) {
+            if let Some(aspect) = Aspect::get_by_key(aspect_key) {
                 Ok((aspect, aspect_amount))
             } else {
                 Err(format!("Aspect inventory contains unknown aspect '{}'", aspect_key))
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    aspect::Edition,
+    source::{FtpMode, Protocol},
+};
+
+/// Server settings stored under `[profiles.<name>]` in the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub protocol: Option<Protocol>,
+    pub address: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub ssh_key: Option<PathBuf>,
+    pub tls_pinned_cert: Option<PathBuf>,
+    pub world_dir: Option<String>,
+    pub path_template: Option<String>,
+    pub ftp_mode: Option<FtpMode>,
+    pub rcon_address: Option<String>,
+    pub rcon_password: Option<String>,
+    pub edition: Option<Edition>,
+}
+
+impl Profile {
+    /// Server settings from `THAUM_FTP_ADDRESS`, `THAUM_FTP_USERNAME`, `THAUM_FTP_PASSWORD`, `THAUM_RCON_ADDRESS`
+    /// and `THAUM_RCON_PASSWORD`, looked up through `var`.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Profile {
+        Profile {
+            address: var("THAUM_FTP_ADDRESS"),
+            username: var("THAUM_FTP_USERNAME"),
+            password: var("THAUM_FTP_PASSWORD"),
+            rcon_address: var("THAUM_RCON_ADDRESS"),
+            rcon_password: var("THAUM_RCON_PASSWORD"),
+            ..Profile::default()
+        }
+    }
+
+    /// Fills the settings missing from this profile with those of the fallback.
+    pub fn or(self, fallback: Profile) -> Profile {
+        Profile {
+            protocol: self.protocol.or(fallback.protocol),
+            address: self.address.or(fallback.address),
+            username: self.username.or(fallback.username),
+            password: self.password.or(fallback.password),
+            ssh_key: self.ssh_key.or(fallback.ssh_key),
+            tls_pinned_cert: self.tls_pinned_cert.or(fallback.tls_pinned_cert),
+            world_dir: self.world_dir.or(fallback.world_dir),
+            path_template: self.path_template.or(fallback.path_template),
+            ftp_mode: self.ftp_mode.or(fallback.ftp_mode),
+            rcon_address: self.rcon_address.or(fallback.rcon_address),
+            rcon_password: self.rcon_password.or(fallback.rcon_password),
+            edition: self.edition.or(fallback.edition),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    const FILE_NAME: &'static str = "config.toml";
+
+    /// `<config dir>/thaumcraft-research-solver/config.toml`, e.g. `~/.config` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(ConfigFile::FILE_NAME))
+    }
+
+    /// Loads the config file at the given path, or at the default path if none is given.
+    /// A missing default config file is not an error, a missing explicitly given one is.
+    pub fn load(path: Option<&Path>) -> Result<ConfigFile, String> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match ConfigFile::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(ConfigFile::default()),
+            },
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) if !explicit && !path.exists() => return Ok(ConfigFile::default()),
+            Err(error) => return Err(format!("Could not read config file '{}': {}", path.display(), error)),
+        };
+
+        let config: ConfigFile = toml::from_str(&content).map_err(|error| format!("Invalid config file '{}': {}", path.display(), error))?;
+        if config.profiles.values().any(|profile| profile.password.is_some() || profile.rcon_password.is_some()) {
+            ConfigFile::warn_if_readable_by_others(&path);
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the requested profile, or the default profile when no name is given.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Profile '{}' does not exist in the config file", name)),
+            None => Ok(Profile::default()),
+        }
+    }
+
+    #[cfg(unix)]
+    fn warn_if_readable_by_others(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                println!("Warning: config file '{}' contains passwords but is readable by other users", path.display());
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn warn_if_readable_by_others(_path: &Path) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    const CONFIG: &str = r#"
+default_profile = "home"
+
+[profiles.home]
+protocol = "sftp"
+address = "home.example:2222"
+username = "alice"
+
+[profiles.club]
+address = "club.example"
+username = "bob"
+rcon_address = "club.example:25575"
+"#;
+
+    fn config() -> ConfigFile {
+        toml::from_str(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn profiles_are_selected_by_name_or_default() {
+        let config = config();
+        assert_eq!(config.profile(Some("club")).unwrap().username.as_deref(), Some("bob"));
+
+        let home = config.profile(None).unwrap();
+        assert_eq!(home.protocol, Some(Protocol::Sftp));
+        assert_eq!(home.address.as_deref(), Some("home.example:2222"));
+
+        let error = config.profile(Some("work")).unwrap_err();
+        assert_eq!(error, "Profile 'work' does not exist in the config file");
+        assert!(ConfigFile::default().profile(None).unwrap().address.is_none());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<ConfigFile>("[profiles.home]\nadress = \"home.example\"\n").is_err());
+        assert!(toml::from_str::<ConfigFile>("default = \"home\"\n").is_err());
+    }
+
+    #[test]
+    fn profiles_win_over_the_environment() {
+        let env = |name: &str| match name {
+            "THAUM_FTP_ADDRESS" => Some("env.example".to_owned()),
+            "THAUM_FTP_PASSWORD" => Some("env-secret".to_owned()),
+            "THAUM_RCON_ADDRESS" => Some("env.example:25575".to_owned()),
+            _ => None,
+        };
+
+        let club = config().profile(Some("club")).unwrap().or(Profile::from_env(env));
+        assert_eq!(club.address.as_deref(), Some("club.example"));
+        assert_eq!(club.rcon_address.as_deref(), Some("club.example:25575"));
+        assert_eq!(club.password.as_deref(), Some("env-secret"));
+        assert_eq!(club.username.as_deref(), Some("bob"));
+        assert!(club.rcon_password.is_none());
+    }
+
+    #[test]
+    fn explicit_config_files_have_to_exist() {
+        let path = std::env::temp_dir().join(format!("{}-{}-config.toml", env!("CARGO_PKG_NAME"), process::id()));
+        assert!(ConfigFile::load(Some(&path)).unwrap_err().starts_with("Could not read config file"));
+
+        fs::write(&path, CONFIG).unwrap();
+        let loaded = ConfigFile::load(Some(&path));
+        fs::write(&path, "[profiles.home]\nport = 21\n").unwrap();
+        let invalid = ConfigFile::load(Some(&path));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().profiles.len(), 2);
+        assert!(invalid.unwrap_err().starts_with("Invalid config file"));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{fmt, str::FromStr};
+
+/// Axial `(q, r)` coordinates of a hex on a research note, the center being `(0, 0)`.
+/// ThaumCraft 4 stores the same axial form in the `hexq` and `hexr` NBT fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct HexCoord {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Cube form of a hex, `x + y + z == 0`, which makes distances and line drawing symmetric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CubeCoord {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl HexCoord {
+    pub const CENTER: HexCoord = HexCoord { q: 0, r: 0 };
+
+    /// Offsets of the six neighbours, going around counter-clockwise.
+    pub const DIRECTIONS: [HexCoord; 6] = [
+        HexCoord { q: 1, r: 0 },
+        HexCoord { q: 1, r: -1 },
+        HexCoord { q: 0, r: -1 },
+        HexCoord { q: -1, r: 0 },
+        HexCoord { q: -1, r: 1 },
+        HexCoord { q: 0, r: 1 },
+    ];
+
+    pub const fn new(q: i32, r: i32) -> Self {
+        HexCoord { q, r }
+    }
+
+    pub fn to_cube(self) -> CubeCoord {
+        CubeCoord {
+            x: self.q,
+            y: -self.q - self.r,
+            z: self.r,
+        }
+    }
+
+    /// Column and row of the flat-topped grid as drawn by the research table GUI, odd columns shifted down by half a hex.
+    pub fn to_offset(self) -> (i32, i32) {
+        (self.q, self.r + (self.q - (self.q & 1)) / 2)
+    }
+
+    #[allow(dead_code)]
+    pub fn from_offset(column: i32, row: i32) -> Self {
+        HexCoord::new(column, row - (column - (column & 1)) / 2)
+    }
+
+    fn add(self, other: HexCoord) -> HexCoord {
+        HexCoord::new(self.q + other.q, self.r + other.r)
+    }
+
+    fn scale(self, factor: i32) -> HexCoord {
+        HexCoord::new(self.q * factor, self.r * factor)
+    }
+
+    pub fn neighbours(self) -> [HexCoord; 6] {
+        HexCoord::DIRECTIONS.map(|direction| self.add(direction))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_neighbour(self, other: HexCoord) -> bool {
+        self.distance(other) == 1
+    }
+
+    pub fn distance(self, other: HexCoord) -> i32 {
+        let (a, b) = (self.to_cube(), other.to_cube());
+        ((a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs()) / 2
+    }
+
+    pub fn distance_from_center(self) -> i32 {
+        self.distance(HexCoord::CENTER)
+    }
+
+    /// Whether the hex lies on a note of the given radius.
+    pub fn is_within(self, radius: i32) -> bool {
+        self.distance_from_center() <= radius
+    }
+
+    /// The `6 * radius` hexes at exactly `radius` from this one, in order around the ring.
+    pub fn ring(self, radius: i32) -> Vec<HexCoord> {
+        if radius == 0 {
+            return vec![self];
+        }
+
+        let mut hex = self.add(HexCoord::DIRECTIONS[4].scale(radius));
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in HexCoord::DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.add(direction);
+            }
+        }
+
+        ring
+    }
+
+    /// All hexes up to `radius` from this one, ring by ring starting with this hex itself.
+    pub fn spiral(self, radius: i32) -> Vec<HexCoord> {
+        (0..=radius).flat_map(|ring_radius| self.ring(ring_radius)).collect()
+    }
+
+    /// The hexes a straight line from this hex to the other one crosses, both ends included.
+    #[allow(dead_code)]
+    pub fn line(self, other: HexCoord) -> Vec<HexCoord> {
+        let distance = self.distance(other);
+        let (a, b) = (self.to_cube(), other.to_cube());
+        // Nudged off the edges so points exactly between two hexes always round the same way
+        let lerp = |from: i32, to: i32, nudge: f64, t: f64| from as f64 + nudge + (to - from) as f64 * t;
+
+        (0..=distance)
+            .map(|step| {
+                let t = if distance == 0 { 0.0 } else { step as f64 / distance as f64 };
+                CubeCoord::round(lerp(a.x, b.x, 1e-6, t), lerp(a.y, b.y, 2e-6, t), lerp(a.z, b.z, -3e-6, t)).to_axial()
+            })
+            .collect()
+    }
+}
+
+impl CubeCoord {
+    pub fn to_axial(self) -> HexCoord {
+        HexCoord::new(self.x, self.z)
+    }
+
+    /// Rounds fractional cube coordinates to the nearest hex, fixing the component that rounded the most.
+    pub fn round(x: f64, y: f64, z: f64) -> CubeCoord {
+        let (mut rounded_x, mut rounded_y, mut rounded_z) = (x.round(), y.round(), z.round());
+        let (dx, dy, dz) = ((rounded_x - x).abs(), (rounded_y - y).abs(), (rounded_z - z).abs());
+        if dx > dy && dx > dz {
+            rounded_x = -rounded_y - rounded_z;
+        } else if dy > dz {
+            rounded_y = -rounded_x - rounded_z;
+        } else {
+            rounded_z = -rounded_x - rounded_y;
+        }
+
+        CubeCoord {
+            x: rounded_x as i32,
+            y: rounded_y as i32,
+            z: rounded_z as i32,
+        }
+    }
+}
+
+impl fmt::Display for HexCoord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.q, self.r)
+    }
+}
+
+/// Parses `q,r`, optionally in parentheses like the coordinates are printed.
+impl FromStr for HexCoord {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let trimmed = value.trim().trim_start_matches('(').trim_end_matches(')');
+        let (q, r) = trimmed.split_once(',').ok_or_else(|| format!("'{}' is not a hex coordinate like 2,-1", value.trim()))?;
+        let parse = |part: &str| part.trim().parse().map_err(|_| format!("'{}' is not a valid coordinate", part.trim()));
+
+        Ok(HexCoord::new(parse(q)?, parse(r)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn rings_go_around_in_order() {
+        let center = HexCoord::new(2, -1);
+        assert_eq!(center.ring(0), vec![center]);
+        for radius in 1..=4 {
+            let ring = center.ring(radius);
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert_eq!(ring[0], HexCoord::new(2 - radius, -1 + radius));
+            assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+            assert_eq!(ring.iter().collect::<HashSet<_>>().len(), ring.len());
+            for (index, hex) in ring.iter().enumerate() {
+                assert!(hex.is_neighbour(ring[(index + 1) % ring.len()]), "{} and the next hex of ring {}", hex, radius);
+            }
+        }
+    }
+
+    #[test]
+    fn spirals_cover_every_hex_once() {
+        for radius in 0..=4 {
+            let spiral = HexCoord::CENTER.spiral(radius);
+            assert_eq!(spiral.len(), 1 + 3 * radius as usize * (radius as usize + 1));
+            assert_eq!(spiral[0], HexCoord::CENTER);
+            assert!(spiral.iter().all(|hex| hex.is_within(radius)));
+            assert_eq!(spiral.iter().collect::<HashSet<_>>().len(), spiral.len());
+        }
+    }
+
+    #[test]
+    fn offsets_round_trip() {
+        for hex in HexCoord::CENTER.spiral(5) {
+            let (column, row) = hex.to_offset();
+            assert_eq!(HexCoord::from_offset(column, row), hex);
+        }
+        // Odd columns are shifted down on both sides of the center
+        for column in [-3, -1, 1, 3] {
+            let (above, below) = (HexCoord::from_offset(column, -1), HexCoord::from_offset(column, 0));
+            assert!(above.is_neighbour(below));
+            assert!(HexCoord::from_offset(column - 1, 0).is_neighbour(above));
+            assert!(HexCoord::from_offset(column - 1, 0).is_neighbour(below));
+        }
+        assert_eq!(HexCoord::new(-1, 0).to_offset(), (-1, -1));
+        assert_eq!(HexCoord::new(-3, 4).to_offset(), (-3, 2));
+    }
+
+    #[test]
+    fn lines_join_their_ends() {
+        let start = HexCoord::new(-2, 3);
+        assert_eq!(start.line(start), vec![start]);
+        for end in HexCoord::CENTER.spiral(4) {
+            let line = start.line(end);
+            assert_eq!(line.len(), start.distance(end) as usize + 1);
+            assert_eq!((line[0], *line.last().unwrap()), (start, end));
+            assert!(line.windows(2).all(|pair| pair[0].is_neighbour(pair[1])), "{:?}", line);
+        }
+    }
+}
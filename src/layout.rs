@@ -0,0 +1,142 @@
+/// Where the player data lives on the MineCraft server, built from a world directory and a path template.
+#[derive(Debug, Clone)]
+pub struct PlayerDataLayout {
+    template: String,
+}
+
+impl PlayerDataLayout {
+    pub const DEFAULT_WORLD: &'static str = "World";
+    pub const DEFAULT_TEMPLATE: &'static str = "/{world}/playerdata/{username}.thaum";
+    pub const SERVER_PROPERTIES_PATH: &'static str = "/server.properties";
+    pub const USER_CACHE_PATH: &'static str = "/usercache.json";
+
+    const WORLD_PLACEHOLDER: &'static str = "{world}";
+    const USERNAME_PLACEHOLDER: &'static str = "{username}";
+
+    pub fn new(world: &str, template: &str) -> Result<Self, String> {
+        let (_, file_name_template) = template.rsplit_once('/').unwrap_or(("", template));
+        if !file_name_template.contains(PlayerDataLayout::USERNAME_PLACEHOLDER) {
+            return Err(format!(
+                "Path template '{}' must contain {} in its file name",
+                template,
+                PlayerDataLayout::USERNAME_PLACEHOLDER
+            ));
+        }
+
+        Ok(PlayerDataLayout {
+            template: template.replace(PlayerDataLayout::WORLD_PLACEHOLDER, world.trim_matches('/')),
+        })
+    }
+
+    /// Builds the layout, reading `server.properties` through the given download function
+    /// when no world directory was configured.
+    pub fn resolve(world_dir: Option<&str>, template: &str, download: impl FnOnce(&str) -> Option<Vec<u8>>) -> Result<Self, String> {
+        let world = match world_dir {
+            Some(world) => world.to_owned(),
+            None => {
+                let level_name =
+                    download(PlayerDataLayout::SERVER_PROPERTIES_PATH).and_then(|properties| PlayerDataLayout::level_name_from_properties(&String::from_utf8_lossy(&properties)));
+                match level_name {
+                    Some(level_name) => {
+                        println!("Detected world '{}' from server.properties", level_name);
+                        level_name
+                    }
+                    None => PlayerDataLayout::DEFAULT_WORLD.to_owned(),
+                }
+            }
+        };
+
+        PlayerDataLayout::new(&world, template)
+    }
+
+    pub fn thaum_path(&self, username: &str) -> String {
+        self.template.replace(PlayerDataLayout::USERNAME_PLACEHOLDER, username)
+    }
+
+    pub fn player_data_dir(&self) -> &str {
+        self.template.rsplit_once('/').map_or(".", |(dir, _)| dir)
+    }
+
+    /// Vanilla `<uuid>.dat` file next to the `.thaum` files, holding the player's items.
+    pub fn player_dat_path(&self, uuid: &str) -> String {
+        format!("{}/{}.dat", self.player_data_dir(), uuid)
+    }
+
+    /// Extracts the username from a file name in the player data directory, if it matches the template.
+    pub fn username_from_file_name<'a>(&self, file_name: &'a str) -> Option<&'a str> {
+        let file_name = file_name.rsplit('/').next()?;
+        let (_, file_name_template) = self.template.rsplit_once('/').unwrap_or(("", &self.template));
+        let (prefix, suffix) = file_name_template.split_once(PlayerDataLayout::USERNAME_PLACEHOLDER)?;
+
+        file_name.strip_prefix(prefix)?.strip_suffix(suffix).filter(|username| !username.is_empty())
+    }
+
+    /// Reads the `level-name` entry of a `server.properties` file.
+    pub fn level_name_from_properties(properties: &str) -> Option<String> {
+        properties
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "level-name")
+            .map(|(_, value)| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_expand_world_and_username() {
+        let layout = PlayerDataLayout::new("/Survival/", PlayerDataLayout::DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(layout.thaum_path("alice"), "/Survival/playerdata/alice.thaum");
+        assert_eq!(layout.player_data_dir(), "/Survival/playerdata");
+        assert_eq!(layout.player_dat_path("8c6e1a2b"), "/Survival/playerdata/8c6e1a2b.dat");
+
+        let layout = PlayerDataLayout::new("World", "thaum/{username}_{world}.dat").unwrap();
+        assert_eq!(layout.thaum_path("bob"), "thaum/bob_World.dat");
+        assert_eq!(PlayerDataLayout::new("World", "{username}.thaum").unwrap().player_data_dir(), ".");
+    }
+
+    #[test]
+    fn templates_need_the_username_in_the_file_name() {
+        assert!(PlayerDataLayout::new("World", "/{world}/playerdata/alice.thaum").is_err());
+        assert!(PlayerDataLayout::new("World", "/{username}/data.thaum").is_err());
+    }
+
+    #[test]
+    fn resolve_reads_the_world_from_server_properties() {
+        let properties = |content: &'static str| move |path: &str| (path == PlayerDataLayout::SERVER_PROPERTIES_PATH).then(|| content.as_bytes().to_vec());
+
+        let layout = PlayerDataLayout::resolve(None, PlayerDataLayout::DEFAULT_TEMPLATE, properties("level-name=Survival\n")).unwrap();
+        assert_eq!(layout.thaum_path("alice"), "/Survival/playerdata/alice.thaum");
+        let layout = PlayerDataLayout::resolve(Some("Creative"), PlayerDataLayout::DEFAULT_TEMPLATE, properties("level-name=Survival\n")).unwrap();
+        assert_eq!(layout.thaum_path("alice"), "/Creative/playerdata/alice.thaum");
+        let layout = PlayerDataLayout::resolve(None, PlayerDataLayout::DEFAULT_TEMPLATE, |_| None).unwrap();
+        assert_eq!(layout.thaum_path("alice"), "/World/playerdata/alice.thaum");
+    }
+
+    #[test]
+    fn level_name_skips_comments_and_whitespace() {
+        let properties = "#Minecraft server properties\n#level-name=Commented\nmotd=A server\n  level-name = My World  \nlevel-seed=\n";
+        assert_eq!(PlayerDataLayout::level_name_from_properties(properties), Some("My World".to_string()));
+        assert_eq!(PlayerDataLayout::level_name_from_properties("motd=A server\n"), None);
+        assert_eq!(PlayerDataLayout::level_name_from_properties("level-name=\n"), None);
+        assert_eq!(PlayerDataLayout::level_name_from_properties("level-names=Other\n"), None);
+    }
+
+    #[test]
+    fn usernames_come_back_from_file_names() {
+        let layout = PlayerDataLayout::new("World", PlayerDataLayout::DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(layout.username_from_file_name("alice.thaum"), Some("alice"));
+        assert_eq!(layout.username_from_file_name("/World/playerdata/bob.thaum"), Some("bob"));
+        assert_eq!(layout.username_from_file_name("8c6e1a2b.dat"), None);
+        assert_eq!(layout.username_from_file_name(".thaum"), None);
+
+        let layout = PlayerDataLayout::new("World", "/{world}/thaum/player_{username}.dat").unwrap();
+        assert_eq!(layout.username_from_file_name("player_carol.dat"), Some("carol"));
+        assert_eq!(layout.username_from_file_name("carol.dat"), None);
+    }
+}
@@ -1,33 +1,282 @@
 mod aspect;
+mod config;
 mod graph;
+mod hex;
+mod layout;
+mod note;
+mod placement;
+mod player;
+mod rcon;
+mod research;
+mod reserve;
+mod restock;
+mod skill;
 mod solver;
+mod source;
+mod steiner;
+mod table;
+mod team;
+mod watch;
 
-use aspect::{Aspect, AspectInventory};
-use clap::Parser;
-use ftp::FtpStream;
-use nbt::Blob;
+use aspect::{Aspect, AspectInventory, Edition};
+use clap::{Parser, Subcommand};
+use config::{ConfigFile, Profile};
+use hex::HexCoord;
+use layout::PlayerDataLayout;
+use note::ResearchNote;
+use placement::{NoPlan, NotePlan, PlacementSolver};
+use player::PlayerData;
+use rcon::RconConfig;
+use research::{CostEstimate, ResearchCatalog};
+use reserve::{ReservePolicy, Reserves};
+use restock::RestockPlanner;
+use skill::ResearchSkill;
 use solver::Solver;
-use std::{cmp::min, io::Cursor};
+use source::{ArchiveSource, CachedSource, FtpMode, InventorySource, LocalSource, Protocol, RemoteConfig, SavingSource, SourceError};
+use std::{
+    cmp::min,
+    env,
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use steiner::{DistanceConstraint, SteinerSolver, SteinerTree};
+use table::CostTable;
+use team::{Team, TeamMember};
+use watch::Watcher;
 
 /// ThaumCraft Research Solver using weighted paths with your actual aspect inventory
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Actual MineCraft username
-    #[arg(short, long)]
-    username: String,
+    /// Actual MineCraft username, repeat it or separate names by commas to compare a whole team
+    #[arg(short, long, value_delimiter = ',', required_unless_present = "all_players")]
+    username: Vec<String>,
+
+    /// Load every player found in the player data directory
+    #[arg(long)]
+    all_players: bool,
+
+    /// Local directory containing the `.thaum` files, used instead of FTP
+    #[arg(short, long, conflicts_with_all = ["archive", "profile"])]
+    local_dir: Option<PathBuf>,
+
+    /// World backup archive (`.zip`, `.tar` or `.tar.gz`) to read the `.thaum` files from, used instead of FTP
+    #[arg(long, conflicts_with = "profile")]
+    archive: Option<PathBuf>,
+
+    /// Config file with server profiles, defaults to `config.toml` in the user config directory
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-    /// MineCraft server FTP address
+    /// Server profile from the config file, the config's `default_profile` is used when omitted
+    #[arg(short = 'P', long)]
+    profile: Option<String>,
+
+    /// Protocol used to download the player data from the MineCraft server [default: ftp]
+    #[arg(long, value_enum)]
+    protocol: Option<Protocol>,
+
+    /// MineCraft server FTP address, FTP uses port 21 and SFTP port 22 when none is given.
+    /// Falls back to the profile, then to `THAUM_FTP_ADDRESS`
     #[arg(short = 'a', long)]
-    ftp_address: String,
+    ftp_address: Option<String>,
 
-    /// MineCraft server FTP username
+    /// MineCraft server FTP username. Falls back to the profile, then to `THAUM_FTP_USERNAME`
     #[arg(short, long)]
-    ftp_username: String,
+    ftp_username: Option<String>,
 
-    /// MineCraft server FTP password
+    /// MineCraft server FTP password, or the key passphrase when using an SSH key. Prefer the config file or
+    /// `THAUM_FTP_PASSWORD`, used in that order, you are prompted for it when it is not set anywhere
     #[arg(short = 'p', long)]
-    ftp_password: String,
+    ftp_password: Option<String>,
+
+    /// Private key file used to authenticate over SFTP instead of the password
+    #[arg(long)]
+    ssh_key: Option<PathBuf>,
+
+    /// World directory on the server, detected from `level-name` in `server.properties` when omitted
+    #[arg(short, long)]
+    world_dir: Option<String>,
+
+    /// Remote path of the `.thaum` files, `{world}` and `{username}` are replaced [default: /{world}/playerdata/{username}.thaum]
+    #[arg(long)]
+    path_template: Option<String>,
+
+    /// PEM certificate to pin for FTPS, the server has to present exactly this certificate, whatever its issuer and host name
+    #[arg(long)]
+    tls_pinned_cert: Option<PathBuf>,
+
+    /// MineCraft server RCON address, port 25575 when none is given. When set, the server runs `save-all`
+    /// before the player data is read, as ThaumCraft only writes `.thaum` files on save or logout.
+    /// Falls back to the profile, then to `THAUM_RCON_ADDRESS`
+    #[arg(long, conflicts_with = "archive")]
+    rcon_address: Option<String>,
+
+    /// RCON password, `rcon.password` in `server.properties`. Falls back to the profile, then to `THAUM_RCON_PASSWORD`,
+    /// you are prompted for it when it is not set anywhere
+    #[arg(long)]
+    rcon_password: Option<String>,
+
+    /// How FTP data connections are opened [default: passive]
+    #[arg(long, value_enum)]
+    ftp_mode: Option<FtpMode>,
+
+    /// Timeout for connecting to the server and for every read, e.g. `30s`
+    #[arg(long, default_value = "30s", value_parser = parse_nonzero_duration)]
+    timeout: Duration,
+
+    /// How many times to reconnect and retry after a network failure, waiting twice as long each time
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// How long a cached inventory is used without downloading it again, e.g. `90s`, `10m` or `2h`
+    #[arg(long, default_value = "0s", value_parser = parse_duration)]
+    cache_ttl: Duration,
+
+    /// Never read or write the local inventory and cost table caches
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Check the server for changed inventories at this interval while the session runs, e.g. `30s`.
+    /// Files are only downloaded again when their modification time changed, if the source reports one
+    #[arg(long, value_parser = parse_nonzero_duration, conflicts_with_all = ["archive", "cache_ttl"])]
+    watch: Option<Duration>,
+
+    /// Minimal amount of an aspect to keep, e.g. `praecantatio=50`, can be repeated
+    #[arg(short, long = "reserve", value_parser = parse_aspect_amount)]
+    reserves: Vec<(Aspect, u16)>,
+
+    /// How the solver treats steps that would push an aspect below its reserve
+    #[arg(long, value_enum, default_value_t)]
+    reserve_policy: ReservePolicy,
+
+    /// ThaumCraft version of the server [default: tc4]
+    #[arg(long, value_enum)]
+    edition: Option<Edition>,
+
+    /// Player UUID naming their `playerdata/<uuid>.dat`, looked up in the server's `usercache.json` when omitted
+    #[arg(long)]
+    uuid: Option<String>,
+
+    /// List this many of the cheapest paths for every length, ranked by price, instead of only the paths tying the cheapest
+    #[arg(short = 'k', long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    best: Option<usize>,
+
+    /// Research skill used for expected costs, detected from completed research when omitted. It scales every price
+    /// by the same factor, so it changes the expected costs shown but never which paths are the cheapest
+    #[arg(short = 's', long, value_enum)]
+    research_skill: Option<ResearchSkill>,
+
+    /// JSON dump of the pack's research entries, defaults to `research.json` in the user config directory
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the aspect inventory and flag aspects below their reserve
+    Inventory,
+    /// Print the research notes carried in the inventory or ender chest
+    Notes {
+        /// Also plan where to place which aspect to complete every unfinished note
+        #[arg(long)]
+        solve: bool,
+        /// Solve a note saved as text instead of the carried ones, can be repeated.
+        /// The file holds `radius 3`, then lines like `2,-1 aer` for the fixed aspects and `1,1 #` for blocked hexes
+        #[arg(short, long = "file")]
+        files: Vec<PathBuf>,
+    },
+    /// Plan research table combinations that restock aspects to the given levels
+    Restock {
+        /// Desired amount of an aspect, e.g. `lux=40`, can be repeated
+        #[arg(short, long = "target", required = true, value_parser = parse_aspect_amount)]
+        targets: Vec<(Aspect, u16)>,
+    },
+    /// Print the cheapest price and number of cheapest paths between every pair of aspects
+    Pairs {
+        /// Number of aspects in the path, both ends included
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(2..=CostTable::MAX_LENGTH as i64))]
+        length: u8,
+        /// Only list the pairs starting at this aspect
+        #[arg(long, value_parser = parse_aspect)]
+        from: Option<Aspect>,
+    },
+    /// Find the cheapest network of aspect chains connecting three or more aspects, like a research note needs
+    Connect {
+        /// Aspects to connect
+        #[arg(required = true, num_args = 2.., value_parser = parse_aspect)]
+        aspects: Vec<Aspect>,
+        /// Minimal hex distance between two of the aspects on the note, e.g. `ignis:aqua=3`, can be repeated
+        #[arg(long = "min-distance", value_parser = parse_distance_constraint)]
+        min_distances: Vec<DistanceConstraint>,
+    },
+    /// Compare the research catalog with the research completed by the players
+    Research {
+        #[command(subcommand)]
+        action: ResearchAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ResearchAction {
+    /// List the research of the catalog that is still open, by category
+    List,
+    /// Estimate the aspects a research note costs by connecting its starting aspects
+    Cost {
+        /// Research key from the catalog, case insensitive
+        key: String,
+    },
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit_secs) = match value.char_indices().last() {
+        Some((index, 's')) => (&value[..index], 1),
+        Some((index, 'm')) => (&value[..index], 60),
+        Some((index, 'h')) => (&value[..index], 60 * 60),
+        Some((index, 'd')) => (&value[..index], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("'{}' is not a valid duration like 90s, 10m or 2h", value))?;
+    let secs = number.checked_mul(unit_secs).ok_or_else(|| format!("'{}' is too long", value))?;
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn parse_nonzero_duration(value: &str) -> Result<Duration, String> {
+    match parse_duration(value)? {
+        Duration::ZERO => Err(format!("'{}' has to be longer than zero", value.trim())),
+        duration => Ok(duration),
+    }
+}
+
+fn parse_aspect(value: &str) -> Result<Aspect, String> {
+    Aspect::get_by_key(value.trim()).ok_or_else(|| format!("Aspect '{}' does not exist", value.trim()))
+}
+
+fn parse_aspect_amount(value: &str) -> Result<(Aspect, u16), String> {
+    let (aspect_str, amount_str) = value.split_once('=').ok_or_else(|| format!("'{}' is not in the form aspect=amount", value))?;
+    let aspect = parse_aspect(aspect_str)?;
+    let amount = amount_str.trim().parse().map_err(|_| format!("'{}' is not a valid amount", amount_str.trim()))?;
+
+    Ok((aspect, amount))
+}
+
+fn parse_distance_constraint(value: &str) -> Result<DistanceConstraint, String> {
+    let (pair, distance) = value.split_once('=').ok_or_else(|| format!("'{}' is not in the form aspect:aspect=distance", value))?;
+    let (aspect_a, aspect_b) = pair.split_once(':').ok_or_else(|| format!("'{}' is not in the form aspect:aspect=distance", value))?;
+    let distance = distance.trim().parse().map_err(|_| format!("'{}' is not a valid distance", distance.trim()))?;
+
+    Ok(DistanceConstraint {
+        a: parse_aspect(aspect_a)?,
+        b: parse_aspect(aspect_b)?,
+        distance,
+    })
 }
 
 fn yes_or_no() -> bool {
@@ -35,16 +284,13 @@ fn yes_or_no() -> bool {
     match std::io::stdin().read_line(&mut input) {
         Ok(_) => {
             let normalized_input = input.trim().to_lowercase();
-            match normalized_input.as_str() {
-                "yes" | "y" => true,
-                _ => false,
-            }
+            matches!(normalized_input.as_str(), "yes" | "y")
         }
         Err(error) => panic!("Error reading input: {}", error),
     }
 }
 
-fn find_aspect(msg: &str) -> Aspect {
+fn find_aspect(msg: &str, edition: Edition) -> Aspect {
     use std::io::{self, Write};
 
     let mut aspect_str = String::new();
@@ -58,7 +304,11 @@ fn find_aspect(msg: &str) -> Aspect {
         io::stdin().read_line(&mut aspect_str).unwrap();
         aspect_str = aspect_str.trim().to_owned();
 
-        aspect = match Aspect::from_str_fuzzy(&aspect_str) {
+        if let Some(Err(error)) = Aspect::get_by_key(&aspect_str).map(|aspect| edition.check(aspect)) {
+            println!("{}!", error);
+            continue;
+        }
+        aspect = match Aspect::from_str_fuzzy(&aspect_str, edition) {
             Some((aspect, 1.0)) => Some(aspect),
             Some((aspect, _)) => {
                 println!("Did you mean '{:?}'? y/n", aspect);
@@ -78,83 +328,832 @@ fn find_aspect(msg: &str) -> Aspect {
     aspect.unwrap()
 }
 
-fn read_u8(msg: &str, max: u8) -> u8 {
+/// Reads either the number of hexes between two aspects, or the coordinates of both, into the length of the path.
+fn read_path_length(msg: &str) -> u8 {
     use std::io::{self, Write};
 
-    let mut value_str = String::new();
-    let mut value: Option<u8> = None;
-    while value.is_none() {
-        value_str.clear();
-
+    loop {
+        let mut value_str = String::new();
         print!("{}", msg);
         io::stdout().flush().unwrap();
         io::stdin().read_line(&mut value_str).unwrap();
-        value_str = value_str.trim().to_string();
 
-        value = match value_str.parse() {
-            Ok(value) if value <= max => Some(value),
-            _ => {
-                print!("'{}' is not a valid integer between 0 and {}! ", value_str, max);
-                None
+        match parse_path_length(value_str.trim()) {
+            Ok(length) => return length,
+            Err(error) => print!("{}! ", error),
+        }
+    }
+}
+
+fn parse_path_length(value: &str) -> Result<u8, String> {
+    let coordinates: Vec<&str> = value.split_whitespace().collect();
+    match coordinates[..] {
+        [between] if !between.contains(',') => match between.parse::<u8>() {
+            Ok(between) if between <= 8 => Ok(between + 2),
+            _ => Err(format!("'{}' is not a valid integer between 0 and 8", between)),
+        },
+        [a, b] => {
+            let (a, b): (HexCoord, HexCoord) = (a.parse()?, b.parse()?);
+            if let Some(outside) = [a, b].into_iter().find(|hex| !hex.is_within(ResearchNote::MAX_RADIUS)) {
+                return Err(format!("{} is outside of the largest research note", outside));
             }
+            if a == b {
+                return Err("Both aspects are on the same hex".to_owned());
+            }
+            Ok(a.distance(b) as u8 + 1)
         }
+        _ => Err(format!("'{}' is neither a distance nor two coordinates", value)),
+    }
+}
+
+/// Merges the command line with the selected config profile, the command line taking precedence.
+fn resolve_remote_config(args: &Args, profile: Profile) -> Result<RemoteConfig, String> {
+    let protocol = args.protocol.or(profile.protocol).unwrap_or_default();
+    let address = args.ftp_address.clone().or(profile.address).ok_or_else(|| "Server address is not set".to_string())?;
+    let username = args.ftp_username.clone().or(profile.username).ok_or_else(|| "Server username is not set".to_string())?;
+    let ssh_key = args.ssh_key.clone().or(profile.ssh_key);
+    let password = args.ftp_password.clone().or(profile.password);
+
+    Ok(RemoteConfig {
+        protocol,
+        address,
+        username,
+        password,
+        ssh_key,
+        tls_pinned_cert: args.tls_pinned_cert.clone().or(profile.tls_pinned_cert),
+        world_dir: args.world_dir.clone().or(profile.world_dir),
+        path_template: args
+            .path_template
+            .clone()
+            .or(profile.path_template)
+            .unwrap_or_else(|| PlayerDataLayout::DEFAULT_TEMPLATE.to_owned()),
+        ftp_mode: args.ftp_mode.or(profile.ftp_mode).unwrap_or_default(),
+        timeout: args.timeout,
+        retries: args.retries,
+    })
+}
+
+/// Connects to the server, prompting for the password when it is needed but not set anywhere.
+fn connect_to_server(config: &RemoteConfig) -> Result<Box<dyn InventorySource>, SourceError> {
+    let mut config = config.clone();
+    if config.password.is_none() && !(config.protocol == Protocol::Sftp && config.ssh_key.is_some()) {
+        let prompt = format!("Password for {}@{}: ", config.username, config.address);
+        config.password = Some(rpassword::prompt_password(prompt).map_err(|error| SourceError::Other(format!("Could not read password: {}", error)))?);
     }
 
-    value.unwrap()
+    source::connect(&config)
 }
 
-fn download_aspect_inventory_from_ftp(args: &Args) -> Cursor<Vec<u8>> {
-    let mut ftp_stream = FtpStream::connect(args.ftp_address.as_str()).expect("Should connect to FTP");
-    let _ = ftp_stream.login(args.ftp_username.as_str(), args.ftp_password.as_str()).expect("Should login to FTP");
+/// Returns the RCON settings if an RCON address is configured, prompting for the password when it is not set anywhere.
+fn resolve_rcon_config(args: &Args, profile: &Profile) -> Result<Option<RconConfig>, String> {
+    let Some(address) = args.rcon_address.clone().or(profile.rcon_address.clone()) else {
+        return Ok(None);
+    };
+    let password = match args.rcon_password.clone().or(profile.rcon_password.clone()) {
+        Some(password) => password,
+        None => rpassword::prompt_password(format!("RCON password for {}: ", address)).map_err(|error| format!("Could not read password: {}", error))?,
+    };
+
+    Ok(Some(RconConfig {
+        address,
+        password,
+        timeout: args.timeout,
+    }))
+}
 
-    ftp_stream
-        .simple_retr(format!("/World/playerdata/{}.thaum", args.username).as_str())
-        .expect("Should retrieve thaum file from FTP")
+fn with_rcon(source: Box<dyn InventorySource>, rcon: Option<RconConfig>) -> Box<dyn InventorySource> {
+    match rcon {
+        Some(rcon) => Box::new(SavingSource::new(source, rcon)),
+        None => source,
+    }
 }
 
-fn main_loop(solver: &Solver) {
-    let aspect_a = find_aspect("Enter the first aspect: ");
-    let aspect_b = find_aspect("Enter the second aspect: ");
+/// The config profile applies to remote sources only, local directories and archives are configured on the command line.
+/// Environment variables only fill in what the profile leaves out, so they never override a chosen profile.
+fn selected_profile(args: &Args) -> Result<Profile, String> {
+    let env_profile = Profile::from_env(|name| env::var(name).ok());
+    if args.archive.is_some() || args.local_dir.is_some() {
+        return Ok(env_profile);
+    }
 
-    let target_distance: u8 = read_u8("Enter the minimal distance between the two aspects: ", 8) + 2;
+    Ok(ConfigFile::load(args.config.as_deref())?.profile(args.profile.as_deref())?.or(env_profile))
+}
+
+fn open_source(args: &Args, profile: Profile) -> Result<Box<dyn InventorySource>, String> {
+    if let Some(archive) = &args.archive {
+        return Ok(Box::new(ArchiveSource::open(archive.clone())?));
+    }
+    if let Some(dir) = &args.local_dir {
+        let rcon = resolve_rcon_config(args, &profile)?;
+        return Ok(with_rcon(Box::new(LocalSource::new(dir.clone())), rcon));
+    }
+
+    let rcon = resolve_rcon_config(args, &profile)?;
+    let config = resolve_remote_config(args, profile)?;
+    match CachedSource::default_dir().filter(|_| !args.no_cache) {
+        Some(cache_dir) => {
+            let connect_config = config.clone();
+            Ok(Box::new(CachedSource::new(
+                cache_dir,
+                &config,
+                args.cache_ttl,
+                Box::new(move || connect_to_server(&connect_config).map(|source| with_rcon(source, rcon.clone()))),
+            )))
+        }
+        None => Ok(with_rcon(connect_to_server(&config)?, rcon)),
+    }
+}
+
+/// Uses the given UUID, or looks the username up in the server's `usercache.json`.
+fn resolve_uuid(source: &mut dyn InventorySource, username: &str, uuid: Option<&str>) -> Result<String, String> {
+    match uuid {
+        Some(uuid) => Ok(uuid.to_owned()),
+        None => PlayerData::uuid_from_user_cache(&source.read_user_cache()?, username),
+    }
+}
+
+fn load_player_data(source: &mut dyn InventorySource, args: &Args, edition: Edition) -> Result<Vec<PlayerData>, String> {
+    let usernames = match (args.all_players, edition) {
+        (false, _) => args.username.clone(),
+        (true, Edition::Tc4) => source.list_players()?,
+        (true, Edition::Tc6) => PlayerData::usernames_from_user_cache(&source.read_user_cache()?)?,
+    };
+
+    usernames
+        .into_iter()
+        .map(|username| match edition {
+            Edition::Tc4 => {
+                let content = source.read_thaum(&username)?;
+                PlayerData::from_thaum(username, &content)
+            }
+            Edition::Tc6 => {
+                let uuid = resolve_uuid(source, &username, args.uuid.as_deref())?;
+                let content = source.read_player_dat(&uuid)?;
+                PlayerData::from_tc6_dat(username, &content)
+            }
+        })
+        .collect()
+}
+
+fn read_query(edition: Edition) -> (Aspect, Aspect, u8, u8) {
+    let aspect_a = find_aspect("Enter the first aspect: ", edition);
+    let aspect_b = find_aspect("Enter the second aspect: ", edition);
+
+    let target_distance = read_path_length("Enter the minimal distance between the two aspects, or their coordinates like '0,-2 2,0': ");
     let max_distance_increase = min(12 - target_distance, 3);
 
-    println!("\n");
+    (aspect_a, aspect_b, target_distance, max_distance_increase)
+}
+
+fn print_path(solver: &Solver, label: &str, price: u32, path: &[Aspect]) {
+    let placed = path.len().saturating_sub(2);
+    println!(
+        "\t{}Score [{}]: {:?} - consumes {} aspect(s), {:.1} expected",
+        label,
+        Solver::format_price(price),
+        path,
+        placed,
+        solver.research_skill().expected_consumption(placed)
+    );
+
+    let mut out_of_stock: Vec<Aspect> = path[1..path.len() - 1]
+        .iter()
+        .copied()
+        .filter(|&aspect| solver.aspect_inventory().amount_of(aspect) == 0)
+        .collect();
+    out_of_stock.dedup();
+    if !out_of_stock.is_empty() {
+        println!("\t\tOut of stock, combine first: {:?}", out_of_stock);
+    }
+}
 
-    let best_paths = solver.find_paths(aspect_a, aspect_b, target_distance, max_distance_increase);
+fn print_paths(solver: &Solver, aspect_a: Aspect, aspect_b: Aspect, target_distance: u8, max_distance_increase: u8) {
+    let mut path_table = None;
     let mut shortest_price: Option<u32> = None;
-    for increase in 0..max_distance_increase {
-        let paths = best_paths.get(&increase);
-        if let Some(paths) = paths {
-            if paths.paths.len() > 0 {
-                if shortest_price.is_none() {
-                    shortest_price = Some(paths.price);
-                    println!("Shortest paths from {:?} to {:?} are of length {}!", aspect_a, aspect_b, target_distance + increase);
-                }
+    for length in target_distance..target_distance + max_distance_increase {
+        let Some((price, count)) = solver.price_and_count(aspect_a, aspect_b, length) else {
+            continue;
+        };
+        if shortest_price.is_none() {
+            shortest_price = Some(price);
+            println!("Shortest paths from {:?} to {:?} are of length {}!", aspect_a, aspect_b, length);
+        }
 
-                if paths.price > shortest_price.unwrap() {
-                    continue;
-                }
+        if price > shortest_price.unwrap() {
+            continue;
+        }
 
-                println!("Paths from {:?} to {:?} of length {}:", aspect_a, aspect_b, target_distance + increase);
-                for path in &paths.paths {
-                    println!("\tScore [{}]: {:?}", paths.price, path);
-                }
-            }
+        println!("{} path(s) from {:?} to {:?} of length {}:", count, aspect_a, aspect_b, length);
+        let path_table = path_table.get_or_insert_with(|| solver.find_paths(aspect_a, target_distance + max_distance_increase - 1));
+        for path in path_table.paths(aspect_b, length) {
+            print_path(solver, "", price, &path);
+        }
+    }
+
+    if shortest_price.is_none() {
+        println!("No path from {:?} to {:?} can be built with the discovered aspects and reserves!", aspect_a, aspect_b);
+    }
+}
+
+fn print_best_paths(solver: &Solver, aspect_a: Aspect, aspect_b: Aspect, target_distance: u8, max_distance_increase: u8, count: usize) {
+    let mut found = false;
+    for length in target_distance..target_distance + max_distance_increase {
+        let best_paths = solver.find_best_paths(aspect_a, aspect_b, length, count);
+        if best_paths.is_empty() {
+            continue;
+        }
+        found = true;
+
+        println!("Best {} path(s) from {:?} to {:?} of length {}:", best_paths.len(), aspect_a, aspect_b, length);
+        for (rank, (price, path)) in best_paths.iter().enumerate() {
+            print_path(solver, &format!("{}. ", rank + 1), *price, path);
         }
     }
 
+    if !found {
+        println!("No path from {:?} to {:?} can be built with the discovered aspects and reserves!", aspect_a, aspect_b);
+    }
+}
+
+/// Lists the `best` cheapest paths of every length when given, otherwise all paths tying the cheapest price.
+fn print_query_result(solver: &Solver, aspect_a: Aspect, aspect_b: Aspect, target_distance: u8, max_distance_increase: u8, best: Option<usize>) {
+    match best {
+        Some(count) => print_best_paths(solver, aspect_a, aspect_b, target_distance, max_distance_increase, count),
+        None => print_paths(solver, aspect_a, aspect_b, target_distance, max_distance_increase),
+    }
+}
+
+fn main_loop(solver: &Mutex<Solver>, edition: Edition, best: Option<usize>) {
+    let (aspect_a, aspect_b, target_distance, max_distance_increase) = read_query(edition);
+
+    println!("\n");
+
+    print_query_result(&solver.lock().unwrap(), aspect_a, aspect_b, target_distance, max_distance_increase, best);
+
     println!("\n");
 }
 
+fn team_loop(team: &Mutex<Team>, edition: Edition, best: Option<usize>) {
+    let (aspect_a, aspect_b, target_distance, max_distance_increase) = read_query(edition);
+
+    println!("\n");
+
+    let team = team.lock().unwrap();
+    let ranking = team.rank_by_price(aspect_a, aspect_b, target_distance, max_distance_increase);
+    if ranking.is_empty() {
+        println!("Nobody in the team can connect {:?} to {:?}!", aspect_a, aspect_b);
+    } else {
+        println!(
+            "Teammates able to connect {:?} to {:?}, cheapest first, priced against the pooled inventory:",
+            aspect_a, aspect_b
+        );
+        for (position, (member, length, price)) in ranking.iter().enumerate() {
+            println!("\t{}. {} - Team score [{}] with length {}", position + 1, member.name, Solver::format_price(*price), length);
+        }
+
+        let (cheapest_member, _, _) = ranking[0];
+        println!("\nPaths for {}, priced against the pooled inventory:", cheapest_member.name);
+        print_query_result(&cheapest_member.solver, aspect_a, aspect_b, target_distance, max_distance_increase, best);
+    }
+
+    println!("\n");
+}
+
+fn print_research_skill(name: &str, research_skill: ResearchSkill) {
+    if research_skill != ResearchSkill::None {
+        println!(
+            "Research skill of {}: {:?}, prices expect {}% of the placed aspects to be consumed. This scales every path alike, the cheapest paths stay the same.",
+            name,
+            research_skill,
+            research_skill.consume_percent()
+        );
+        println!();
+    }
+}
+
+fn print_inventory_report(name: &str, aspect_inventory: &AspectInventory, reserves: &Reserves) {
+    println!("Aspect inventory of {}:", name);
+    let mut undiscovered = Vec::new();
+    for aspect in aspect_inventory.edition().aspects() {
+        if !aspect_inventory.is_discovered(aspect) {
+            undiscovered.push(aspect);
+            continue;
+        }
+
+        let amount = aspect_inventory.amount_of(aspect);
+        let threshold = reserves.threshold_of(aspect);
+        let empty_marker = if amount == 0 { " EMPTY" } else { "" };
+        if reserves.is_below(aspect_inventory, aspect) {
+            println!("\t{:<14} {:>5} / {:<5} LOW{}", aspect.display_name(), amount, threshold, empty_marker);
+        } else if threshold > 0 {
+            println!("\t{:<14} {:>5} / {:<5}", aspect.display_name(), amount, threshold);
+        } else {
+            println!("\t{:<14} {:>5}{}", aspect.display_name(), amount, empty_marker);
+        }
+    }
+
+    if !undiscovered.is_empty() {
+        let names: Vec<String> = undiscovered.iter().map(Aspect::display_name).collect();
+        println!("Undiscovered aspects: {}", names.join(", "));
+    }
+
+    let low_stock = reserves.low_stock(aspect_inventory);
+    if low_stock.is_empty() {
+        println!("All aspects are above their reserve.");
+    } else {
+        println!("{} aspect(s) below their reserve:", low_stock.len());
+        for (aspect, amount, threshold) in low_stock {
+            println!("\t{:?} is missing {}", aspect, threshold - amount);
+        }
+    }
+    println!();
+}
+
+fn read_research_notes(source: &mut dyn InventorySource, username: &str, uuid: Option<&str>) -> Result<Vec<ResearchNote>, String> {
+    let uuid = resolve_uuid(source, username, uuid)?;
+    ResearchNote::find_in_player_dat(&source.read_player_dat(&uuid)?)
+}
+
+fn print_research_notes(name: &str, notes: &[ResearchNote]) {
+    let format_aspects = |aspects: Vec<(HexCoord, Aspect)>| {
+        let aspects: Vec<String> = aspects.iter().map(|(hex, aspect)| format!("{:?} at {}", aspect, hex)).collect();
+        aspects.join(", ")
+    };
+
+    println!("Research notes of {}:", name);
+    if notes.is_empty() {
+        println!("\tNo research notes found.");
+    }
+    for note in notes {
+        let complete_marker = if note.complete { ", complete" } else { "" };
+        println!("\t{} (radius {}{})", note.key, note.radius, complete_marker);
+        println!("\t\tFixed: {}", format_aspects(note.fixed_aspects()));
+
+        let placed = note.placed_aspects();
+        if !placed.is_empty() {
+            println!("\t\tPlaced: {}", format_aspects(placed));
+        }
+        let blocked: Vec<String> = note.blocked_hexes().iter().map(HexCoord::to_string).collect();
+        if !blocked.is_empty() {
+            println!("\t\tBlocked: {}", blocked.join(", "));
+        }
+    }
+    println!();
+}
+
+fn print_pair_costs(name: &str, solver: &Solver, length: u8, from: Option<Aspect>) {
+    let Some(cost_table) = solver.cost_table() else {
+        return;
+    };
+    let aspect_inventory = solver.aspect_inventory();
+
+    println!("Cheapest paths of length {} for {}:", length, name);
+    for &start in cost_table.aspects() {
+        if from.is_some_and(|from| from != start) || (from.is_none() && !aspect_inventory.is_discovered(start)) {
+            continue;
+        }
+
+        let mut reachable = Vec::new();
+        let mut unreachable = Vec::new();
+        for &end in cost_table.aspects() {
+            match cost_table.price(start, end, length) {
+                Some(price) => reachable.push((end, price, cost_table.count(start, end, length))),
+                None => unreachable.push(end.display_name()),
+            }
+        }
+        reachable.sort_by_key(|&(end, price, _)| (price, end.display_name()));
+
+        println!("\tFrom {:?}:", start);
+        for (end, price, count) in reachable {
+            println!("\t\t{:<14} Score [{}], {} path(s)", end.display_name(), Solver::format_price(price), count);
+        }
+        if !unreachable.is_empty() {
+            println!("\t\tCannot reach: {}", unreachable.join(", "));
+        }
+    }
+    println!();
+}
+
+fn print_network(name: &str, solver: &Solver, aspects: &[Aspect], tree: Option<SteinerTree>) {
+    let Some(tree) = tree else {
+        println!("{} cannot connect {:?} with the discovered aspects and reserves!", name, aspects);
+        println!();
+        return;
+    };
+
+    let method = if tree.exact { "cheapest" } else { "heuristic, respecting the distances" };
+    println!("Network connecting {:?} for {} ({}):", aspects, name, method);
+    println!(
+        "\tScore [{}] - consumes {} aspect(s), {:.1} expected",
+        Solver::format_price(tree.price),
+        tree.placed_count(),
+        solver.research_skill().expected_consumption(tree.placed_count())
+    );
+    for chain in tree.chains() {
+        println!("\t{:?}", chain);
+    }
+    for constraint in &tree.unmet_constraints {
+        println!("\tCould not keep {:?} and {:?} at distance {}", constraint.a, constraint.b, constraint.distance);
+    }
+    println!();
+}
+
+fn print_note_plan(solver: &Solver, note: &ResearchNote, plan: Result<NotePlan, NoPlan>) {
+    let plan = match plan {
+        Ok(plan) => plan,
+        Err(NoPlan::Impossible) => {
+            println!("Research note {} cannot be completed with the discovered aspects and reserves!", note.key);
+            println!();
+            return;
+        }
+        Err(NoPlan::NotFound) => {
+            println!("No plan found for research note {}: the cheapest connection needs two aspects on one hex", note.key);
+            println!("and the greedy search found no other way, the note may still be solvable by hand.");
+            println!();
+            return;
+        }
+    };
+
+    let method = if plan.exact { "cheapest" } else { "greedy" };
+    println!("Plan for {} ({}):", note.key, method);
+    println!(
+        "\tScore [{}] - consumes {} aspect(s), {:.1} expected",
+        Solver::format_price(plan.price),
+        plan.placements.len(),
+        solver.research_skill().expected_consumption(plan.placements.len())
+    );
+    for placement in &plan.placements {
+        let (column, row) = placement.hex.to_offset();
+        println!("\tPlace {:?} at {}, column {} row {}", placement.aspect, placement.hex, column, row);
+    }
+    println!();
+}
+
+fn print_restock_plan(name: &str, aspect_inventory: &AspectInventory, reserves: &Reserves, targets: &[(Aspect, u16)]) {
+    let plan = RestockPlanner::new(aspect_inventory, reserves, targets.iter().cloned().collect()).plan();
+
+    println!("Restock plan for {}:", name);
+    if plan.combinations.is_empty() {
+        println!("\tNothing to combine.");
+    }
+    for (step, combination) in plan.combinations.iter().enumerate() {
+        let (component_a, component_b) = combination.components;
+        println!(
+            "\t{}. Combine {:?} + {:?} into {}x {:?}",
+            step + 1,
+            component_a,
+            component_b,
+            combination.count,
+            combination.composite
+        );
+    }
+
+    if plan.unmet_targets.is_empty() {
+        println!("All targets are reached.");
+    } else {
+        println!("Targets that cannot be reached:");
+        for (aspect, missing) in plan.unmet_targets {
+            println!("\t{:?} is still missing {}", aspect, missing);
+        }
+    }
+    println!();
+}
+
+fn load_research_catalog(args: &Args) -> Result<ResearchCatalog, String> {
+    let path = match &args.catalog {
+        Some(path) => path.clone(),
+        None => ResearchCatalog::default_path().ok_or("No user config directory, pass the research catalog with --catalog")?,
+    };
+    ResearchCatalog::load(&path)
+}
+
+fn print_open_research(player: &PlayerData, catalog: &ResearchCatalog) {
+    let open_entries = catalog.open_entries(&player.completed_research);
+
+    println!("Open research of {}:", player.name);
+    if open_entries.is_empty() {
+        println!("\tEverything in the catalog is completed.");
+    }
+    let mut category = None;
+    for entry in &open_entries {
+        if category != Some(&entry.category) {
+            category = Some(&entry.category);
+            println!("\t{}:", entry.category);
+        }
+        let aspects: Vec<String> = entry.aspects.iter().map(Aspect::display_name).collect();
+        println!("\t\t{} (complexity {}): {}", entry.key, entry.complexity, aspects.join(", "));
+    }
+    println!();
+}
+
+fn print_research_cost(name: &str, estimate: &CostEstimate) {
+    println!("Estimated cost for {} on a note of radius {}:", name, estimate.radius);
+    for link in &estimate.links {
+        match link.cheapest {
+            Some((length, price)) => println!(
+                "\t{:?} -> {:?} over {} hexes: Score [{}] length {}",
+                link.from,
+                link.to,
+                link.distance,
+                Solver::format_price(price),
+                length
+            ),
+            None => println!("\t{:?} -> {:?} over {} hexes: cannot be connected", link.from, link.to, link.distance),
+        }
+    }
+    match estimate.total_price() {
+        Some(price) => println!("Total score [{}]", Solver::format_price(price)),
+        None => println!("Some aspects cannot be connected with the discovered aspects and reserves!"),
+    }
+    println!();
+}
+
+/// Aspects on the command line are parsed before the edition is known, so aspects of the other edition are rejected here.
+fn check_edition_of_arguments(args: &Args, edition: Edition) -> Result<(), String> {
+    let mut aspects: Vec<Aspect> = args.reserves.iter().map(|&(aspect, _)| aspect).collect();
+    match &args.command {
+        Some(Command::Restock { targets }) => aspects.extend(targets.iter().map(|&(aspect, _)| aspect)),
+        Some(Command::Pairs { from, .. }) => aspects.extend(from),
+        Some(Command::Connect { aspects: terminals, .. }) => aspects.extend(terminals),
+        _ => {}
+    }
+
+    aspects.into_iter().try_for_each(|aspect| edition.check(aspect).map(|_| ()))
+}
+
 fn main() {
-    let args = Args::parse();
-    let mut aspect_inventory_file = download_aspect_inventory_from_ftp(&args);
-    let blob = Blob::from_gzip_reader(&mut aspect_inventory_file).unwrap();
-    let aspect_inventory = AspectInventory::from_nbt(blob).unwrap();
-    let solver = Solver::new(aspect_inventory);
+    if let Err(error) = run(Args::parse()) {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let reserves = Reserves::new(args.reserves.iter().cloned().collect(), args.reserve_policy);
+    if args.uuid.is_some() && (args.all_players || args.username.len() > 1) {
+        return Err("--uuid can only be used with a single player".to_string());
+    }
+
+    let profile = selected_profile(&args)?;
+    let edition = args.edition.or(profile.edition).unwrap_or_default();
+    check_edition_of_arguments(&args, edition)?;
+    if edition == Edition::Tc6 && args.watch.is_some() {
+        return Err("Watch mode only supports ThaumCraft 4 .thaum files".to_string());
+    }
+
+    let table_dir = CostTable::default_dir().filter(|_| !args.no_cache);
+    let mut source = open_source(&args, profile)?;
+    let mut players = load_player_data(source.as_mut(), &args, edition)?;
+    if edition == Edition::Tc6 {
+        println!("ThaumCraft 6 keeps no aspect pool per player, all aspects count as equally available and paths are ranked by length.");
+    }
+    if players.is_empty() {
+        println!("No player data found!");
+        return Ok(());
+    }
+
+    match &args.command {
+        Some(Command::Inventory) => {
+            for player in &players {
+                print_inventory_report(&player.name, &player.aspect_inventory, &reserves);
+            }
+            return Ok(());
+        }
+        Some(Command::Notes { solve, files }) => {
+            if edition == Edition::Tc6 {
+                return Err("ThaumCraft 6 researches with theorycrafting cards, it has no research notes".to_string());
+            }
+            let note_files = files.iter().map(|path| ResearchNote::load(path)).collect::<Result<Vec<_>, _>>()?;
+            for player in &players {
+                let notes = if note_files.is_empty() {
+                    read_research_notes(source.as_mut(), &player.name, args.uuid.as_deref())?
+                } else {
+                    note_files.clone()
+                };
+                print_research_notes(&player.name, &notes);
+                if *solve || !note_files.is_empty() {
+                    let research_skill = args.research_skill.unwrap_or_else(|| ResearchSkill::from_completed_research(&player.completed_research));
+                    let solver = Solver::new(player.aspect_inventory.clone(), reserves.clone(), research_skill);
+                    for note in notes.iter().filter(|note| !note.complete) {
+                        match PlacementSolver::new(&solver, note) {
+                            Ok(placement_solver) => print_note_plan(&solver, note, placement_solver.solve()),
+                            Err(error) => {
+                                println!("{}, skipping it.", error);
+                                println!();
+                            }
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Restock { targets }) => {
+            for player in &players {
+                print_restock_plan(&player.name, &player.aspect_inventory, &reserves, targets);
+            }
+            return Ok(());
+        }
+        Some(Command::Pairs { length, from }) => {
+            for player in &players {
+                let research_skill = args.research_skill.unwrap_or_else(|| ResearchSkill::from_completed_research(&player.completed_research));
+                let mut solver = Solver::new(player.aspect_inventory.clone(), reserves.clone(), research_skill);
+                solver.precompute_costs(table_dir.as_deref());
+                print_pair_costs(&player.name, &solver, *length, *from);
+            }
+            return Ok(());
+        }
+        Some(Command::Connect { aspects, min_distances }) => {
+            for player in &players {
+                let research_skill = args.research_skill.unwrap_or_else(|| ResearchSkill::from_completed_research(&player.completed_research));
+                let solver = Solver::new(player.aspect_inventory.clone(), reserves.clone(), research_skill);
+                let tree = SteinerSolver::new(&solver, aspects, min_distances.clone())?.solve();
+                print_network(&player.name, &solver, aspects, tree);
+            }
+            return Ok(());
+        }
+        Some(Command::Research { action }) => {
+            let catalog = load_research_catalog(&args)?;
+            match action {
+                ResearchAction::List => {
+                    for player in &players {
+                        print_open_research(player, &catalog);
+                    }
+                }
+                ResearchAction::Cost { key } => {
+                    if edition == Edition::Tc6 {
+                        return Err("ThaumCraft 6 researches with theorycrafting cards, it has no research notes to estimate".to_string());
+                    }
+                    let entry = catalog.get(key).ok_or_else(|| format!("Research {} is not in the catalog", key))?;
+                    for player in &players {
+                        if player.completed_research.contains(&entry.key) {
+                            println!("{} already completed {}.", player.name, entry.key);
+                        }
+                        let research_skill = args.research_skill.unwrap_or_else(|| ResearchSkill::from_completed_research(&player.completed_research));
+                        let solver = Solver::new(player.aspect_inventory.clone(), reserves.clone(), research_skill);
+                        print_research_cost(&player.name, &CostEstimate::estimate(entry, &solver));
+                    }
+                }
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let research_skill_of = |player: &PlayerData| args.research_skill.unwrap_or_else(|| ResearchSkill::from_completed_research(&player.completed_research));
+
+    if players.len() == 1 {
+        let player = players.remove(0);
+        let research_skill = research_skill_of(&player);
+        print_research_skill(&player.name, research_skill);
+        let mut solver = Solver::new(player.aspect_inventory.clone(), reserves, research_skill);
+        solver.precompute_costs(table_dir.as_deref());
+        let solver = Arc::new(Mutex::new(solver));
+        if let Some(interval) = args.watch {
+            let solver = Arc::clone(&solver);
+            Watcher::new(source, &[player], interval).spawn(move |_, aspect_inventory| solver.lock().unwrap().set_aspect_inventory(aspect_inventory));
+        }
+
+        loop {
+            main_loop(&solver, edition, args.best);
+        }
+    }
+
+    let members = players
+        .iter()
+        .map(|player| TeamMember {
+            name: player.name.clone(),
+            solver: Solver::new(player.aspect_inventory.clone(), reserves.clone(), research_skill_of(player)),
+        })
+        .collect();
+    let team = Team::new(members, table_dir.as_deref());
+    team.print_inventory_table();
+    println!();
+    for player in &players {
+        print_research_skill(&player.name, research_skill_of(player));
+    }
+
+    let team = Arc::new(Mutex::new(team));
+    if let Some(interval) = args.watch {
+        let team = Arc::clone(&team);
+        Watcher::new(source, &players, interval).spawn(move |name, aspect_inventory| team.lock().unwrap().set_aspect_inventory(name, aspect_inventory));
+    }
 
     loop {
-        main_loop(&solver);
+        team_loop(&team, edition, args.best);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::MemorySource;
+
+    fn args(arguments: &[&str]) -> Args {
+        Args::try_parse_from([env!("CARGO_PKG_NAME")].iter().chain(arguments)).unwrap()
+    }
+
+    #[test]
+    fn parse_duration_accepts_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 10m "), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::ZERO));
+        assert!(parse_duration("10 minutes").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflowing_values() {
+        assert!(parse_duration(&format!("{}d", u64::MAX / 60)).is_err());
+        assert_eq!(parse_duration(&format!("{}s", u64::MAX)), Ok(Duration::from_secs(u64::MAX)));
+    }
+
+    #[test]
+    fn timeouts_have_to_be_longer_than_zero() {
+        assert!(parse_nonzero_duration("0s").is_err());
+        assert!(parse_nonzero_duration("0").is_err());
+        assert_eq!(parse_nonzero_duration("1s"), Ok(Duration::from_secs(1)));
+        assert!(Args::try_parse_from([env!("CARGO_PKG_NAME"), "--timeout", "0s"]).is_err());
+        assert!(Args::try_parse_from([env!("CARGO_PKG_NAME"), "--watch", "0m"]).is_err());
+    }
+
+    #[test]
+    fn command_line_wins_over_profile_and_environment() {
+        let profile = Profile {
+            address: Some("profile.example".to_owned()),
+            username: Some("profile-user".to_owned()),
+            ..Profile::default()
+        };
+        let env = |name: &str| Some(format!("env {}", name));
+
+        let config = resolve_remote_config(&args(&["-f", "cli-user", "-u", "alice"]), profile.or(Profile::from_env(env))).unwrap();
+        assert_eq!(config.username, "cli-user");
+        assert_eq!(config.address, "profile.example");
+        assert_eq!(config.password.as_deref(), Some("env THAUM_FTP_PASSWORD"));
+    }
+
+    #[test]
+    fn load_player_data_reads_the_named_players() {
+        let mut source = MemorySource::default();
+        source.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 12), (Aspect::Ignis, 0)], &["RESEARCHER1"]));
+        source.set_thaum("bob", MemorySource::thaum_file(&[(Aspect::Ordo, 3)], &[]));
+
+        let players = load_player_data(&mut source, &args(&["-u", "bob,alice"]), Edition::Tc4).unwrap();
+        let names: Vec<&str> = players.iter().map(|player| player.name.as_str()).collect();
+        assert_eq!(names, ["bob", "alice"]);
+
+        let alice = &players[1];
+        assert_eq!(alice.aspect_inventory.amount_of(Aspect::Aer), 12);
+        assert!(alice.aspect_inventory.is_discovered(Aspect::Ignis));
+        assert!(!alice.aspect_inventory.is_discovered(Aspect::Ordo));
+        assert!(alice.completed_research.contains("RESEARCHER1"));
+    }
+
+    #[test]
+    fn load_player_data_lists_all_players() {
+        let mut source = MemorySource::default();
+        source.set_thaum("bob", MemorySource::thaum_file(&[(Aspect::Ordo, 3)], &[]));
+        source.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 12)], &[]));
+
+        let players = load_player_data(&mut source, &args(&["--all-players"]), Edition::Tc4).unwrap();
+        let names: Vec<&str> = players.iter().map(|player| player.name.as_str()).collect();
+        assert_eq!(names, ["alice", "bob"]);
+    }
+
+    #[test]
+    fn load_player_data_reports_missing_players() {
+        let mut source = MemorySource::default();
+        source.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 12)], &[]));
+
+        let error = load_player_data(&mut source, &args(&["-u", "alice,carol"]), Edition::Tc4).err().unwrap();
+        assert!(error.starts_with("Not found: No file for 'carol'"), "{}", error);
+    }
+
+    #[test]
+    fn load_player_data_reports_source_failures() {
+        let mut source = MemorySource::default();
+        source.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 12)], &[]));
+        source.fail_next(SourceError::Network("timed out".to_string()));
+
+        let error = load_player_data(&mut source, &args(&["-u", "alice"]), Edition::Tc4).err().unwrap();
+        assert!(error.starts_with("Network failure: timed out"), "{}", error);
+    }
+
+    #[test]
+    fn load_player_data_resolves_tc6_players_through_the_user_cache() {
+        let mut source = MemorySource::default();
+        source.set_user_cache(r#"[{"name": "Carol", "uuid": "0000-carol"}]"#);
+        source.set_player_dat("0000-carol", MemorySource::tc6_dat(&["FIRSTSTEPS"]));
+
+        let players = load_player_data(&mut source, &args(&["--all-players"]), Edition::Tc6).unwrap();
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[0].name, "Carol");
+        assert!(players[0].completed_research.contains("FIRSTSTEPS"));
+        assert_eq!(players[0].aspect_inventory.edition(), Edition::Tc6);
+
+        let players = load_player_data(&mut source, &args(&["-u", "carol"]), Edition::Tc6).unwrap();
+        assert_eq!(players[0].name, "carol");
     }
 }
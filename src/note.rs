@@ -0,0 +1,267 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use nbt::{Blob, Value};
+
+use crate::{
+    aspect::{Aspect, Edition},
+    hex::HexCoord,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hex {
+    Empty,
+    /// Given by the research, cannot be removed
+    Fixed(Aspect),
+    /// Put there by the player
+    Placed(Aspect),
+    /// Inside the radius but not part of the grid, nothing can be placed there
+    Blocked,
+}
+
+/// A ThaumCraft 4 research note as stored in the item NBT: every usable hex of the grid is listed
+/// under `hexgrid` with its type (0 empty, 1 fixed, 2 placed) and aspect.
+#[derive(Debug, Clone)]
+pub struct ResearchNote {
+    pub key: String,
+    pub complete: bool,
+    pub radius: i32,
+    /// Every hex within the radius, including the blocked ones
+    pub hexes: BTreeMap<HexCoord, Hex>,
+}
+
+impl ResearchNote {
+    /// Notes of the most complex research reach this far from the center.
+    pub const MAX_RADIUS: i32 = 4;
+
+    const HEX_EMPTY: i32 = 0;
+    const HEX_FIXED: i32 = 1;
+    const HEX_PLACED: i32 = 2;
+
+    /// Finds all research notes in the main inventory and ender chest of a player `.dat` file.
+    /// Notes that cannot be parsed are skipped with a warning.
+    pub fn find_in_player_dat(content: &[u8]) -> Result<Vec<ResearchNote>, String> {
+        let blob = Blob::from_gzip_reader(&mut &content[..]).map_err(|error| format!("Player data is not valid NBT: {}", error))?;
+
+        let mut notes = Vec::new();
+        for inventory_name in ["Inventory", "EnderItems"] {
+            let Some(Value::List(items)) = blob.get(inventory_name) else {
+                continue;
+            };
+
+            for item in items {
+                let Value::Compound(item) = item else {
+                    continue;
+                };
+                let Some(Value::Compound(tag)) = item.get("tag") else {
+                    continue;
+                };
+                if !tag.contains_key("hexgrid") || !tag.contains_key("key") {
+                    continue;
+                }
+
+                match ResearchNote::from_nbt(tag) {
+                    Ok(note) => notes.push(note),
+                    Err(error) => println!("Warning: skipping research note: {}", error),
+                }
+            }
+        }
+
+        Ok(notes)
+    }
+
+    pub fn from_nbt(tag: &HashMap<String, Value>) -> Result<ResearchNote, String> {
+        let key = match tag.get("key") {
+            Some(Value::String(key)) => key.clone(),
+            _ => return Err("Research note key is missing or not a string".to_string()),
+        };
+        let complete = matches!(tag.get("complete"), Some(Value::Byte(complete)) if *complete != 0);
+        let grid = match tag.get("hexgrid") {
+            Some(Value::List(grid)) => grid,
+            _ => return Err(format!("Research note {} has no valid hex grid", key)),
+        };
+
+        let mut hexes = BTreeMap::new();
+        for entry in grid {
+            let Value::Compound(entry) = entry else {
+                return Err(format!("Hex grid of research note {} contains unexpected NBT element", key));
+            };
+            // ThaumCraft writes bytes, but accept any integer in case an editor widened them.
+            let integer_of = |name: &str| match entry.get(name) {
+                Some(Value::Byte(value)) => Ok(*value as i32),
+                Some(Value::Short(value)) => Ok(*value as i32),
+                Some(Value::Int(value)) => Ok(*value),
+                _ => Err(format!("Hex of research note {} has no valid '{}'", key, name)),
+            };
+            let aspect = match entry.get("aspect") {
+                Some(Value::String(aspect_key)) if !aspect_key.is_empty() => {
+                    Some(Aspect::get_by_key(aspect_key).ok_or_else(|| format!("Research note {} contains unknown aspect '{}'", key, aspect_key))?)
+                }
+                _ => None,
+            };
+
+            let hex = match (integer_of("type")?, aspect) {
+                (ResearchNote::HEX_FIXED, Some(aspect)) => Hex::Fixed(aspect),
+                (ResearchNote::HEX_PLACED, Some(aspect)) => Hex::Placed(aspect),
+                (ResearchNote::HEX_EMPTY, _) => Hex::Empty,
+                (hex_type, _) => return Err(format!("Research note {} has a hex of type {} without aspect", key, hex_type)),
+            };
+            hexes.insert(HexCoord::new(integer_of("hexq")?, integer_of("hexr")?), hex);
+        }
+
+        // The radius is not stored, but blocked hexes never cover a whole ring, so the farthest hex gives it away.
+        let radius = hexes.keys().map(|coord| coord.distance_from_center()).max().unwrap_or_default();
+        for coord in HexCoord::CENTER.spiral(radius) {
+            hexes.entry(coord).or_insert(Hex::Blocked);
+        }
+
+        Ok(ResearchNote { key, complete, radius, hexes })
+    }
+
+    /// Reads a note saved in the text form of [`ResearchNote::parse`], named after the file.
+    pub fn load(path: &Path) -> Result<ResearchNote, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Could not read research note '{}': {}", path.display(), error))?;
+        let key = path.file_stem().map(|stem| stem.to_string_lossy().to_uppercase()).unwrap_or_default();
+
+        ResearchNote::parse(&key, &text).map_err(|error| format!("Invalid research note '{}': {}", path.display(), error))
+    }
+
+    /// Parses the text form of a note: a `radius 3` line, then `2,-1 aer` for every fixed aspect and `1,1 #` for every
+    /// blocked hex, all other hexes within the radius being empty. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(key: &str, text: &str) -> Result<ResearchNote, String> {
+        let mut radius = None;
+        let mut hexes = BTreeMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error_at = |error: String| format!("line {}: {}", index + 1, error);
+
+            let (first, rest) = line.split_once(char::is_whitespace).map(|(first, rest)| (first, rest.trim())).unwrap_or((line, ""));
+            if first.eq_ignore_ascii_case("radius") {
+                if radius.is_some() {
+                    return Err(error_at("the radius is given twice".to_string()));
+                }
+                radius = match rest.parse() {
+                    Ok(value) if (1..=ResearchNote::MAX_RADIUS).contains(&value) => Some(value),
+                    _ => return Err(error_at(format!("'{}' is not a radius between 1 and {}", rest, ResearchNote::MAX_RADIUS))),
+                };
+                continue;
+            }
+
+            let Some(radius) = radius else {
+                return Err(error_at("expected the radius first, like 'radius 3'".to_string()));
+            };
+            let coord: HexCoord = first.parse().map_err(error_at)?;
+            if !coord.is_within(radius) {
+                return Err(error_at(format!("{} is outside of the radius {}", coord, radius)));
+            }
+            let hex = match rest {
+                "" => return Err(error_at(format!("expected an aspect or '#' after {}", first))),
+                "#" => Hex::Blocked,
+                aspect_key => {
+                    let aspect = Aspect::get_by_key(aspect_key).ok_or_else(|| error_at(format!("aspect '{}' does not exist", aspect_key)))?;
+                    Hex::Fixed(Edition::Tc4.check(aspect).map_err(error_at)?)
+                }
+            };
+            if hexes.insert(coord, hex).is_some() {
+                return Err(error_at(format!("{} is given twice", coord)));
+            }
+        }
+
+        let radius = radius.ok_or_else(|| "the radius is missing, like 'radius 3'".to_string())?;
+        for coord in HexCoord::CENTER.spiral(radius) {
+            hexes.entry(coord).or_insert(Hex::Empty);
+        }
+
+        Ok(ResearchNote {
+            key: key.to_string(),
+            complete: false,
+            radius,
+            hexes,
+        })
+    }
+
+    pub fn fixed_aspects(&self) -> Vec<(HexCoord, Aspect)> {
+        self.hexes
+            .iter()
+            .filter_map(|(&coord, hex)| match hex {
+                Hex::Fixed(aspect) => Some((coord, *aspect)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn placed_aspects(&self) -> Vec<(HexCoord, Aspect)> {
+        self.hexes
+            .iter()
+            .filter_map(|(&coord, hex)| match hex {
+                Hex::Placed(aspect) => Some((coord, *aspect)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn blocked_hexes(&self) -> Vec<HexCoord> {
+        self.hexes.iter().filter(|(_, hex)| **hex == Hex::Blocked).map(|(&coord, _)| coord).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> String {
+        ResearchNote::parse("TEST", text).unwrap_err()
+    }
+
+    #[test]
+    fn parses_a_note() {
+        let note = ResearchNote::parse("ALUMENTUM", "# carried by alice\nradius 2\n\n-2,2 ordo\n0,-2 Ignis\n(2,0) potentia\n1,0 #\n").unwrap();
+        assert_eq!(note.key, "ALUMENTUM");
+        assert_eq!(note.radius, 2);
+        assert!(!note.complete);
+        assert_eq!(note.hexes.len(), 19);
+        assert_eq!(
+            note.fixed_aspects(),
+            vec![
+                (HexCoord::new(-2, 2), Aspect::Ordo),
+                (HexCoord::new(0, -2), Aspect::Ignis),
+                (HexCoord::new(2, 0), Aspect::Potentia)
+            ]
+        );
+        assert_eq!(note.blocked_hexes(), vec![HexCoord::new(1, 0)]);
+        assert!(note.placed_aspects().is_empty());
+        assert_eq!(note.hexes[&HexCoord::CENTER], Hex::Empty);
+    }
+
+    #[test]
+    fn rejects_a_missing_or_repeated_radius() {
+        assert_eq!(parse_error("# nothing\n"), "the radius is missing, like 'radius 3'");
+        assert_eq!(parse_error("0,0 ordo\nradius 2\n"), "line 1: expected the radius first, like 'radius 3'");
+        assert_eq!(parse_error("radius 2\n0,0 ordo\nradius 3\n"), "line 3: the radius is given twice");
+        assert_eq!(parse_error("radius 9\n"), "line 1: '9' is not a radius between 1 and 4");
+    }
+
+    #[test]
+    fn rejects_hexes_outside_of_the_radius() {
+        assert_eq!(parse_error("radius 2\n3,0 aer\n"), "line 2: (3, 0) is outside of the radius 2");
+        assert_eq!(parse_error("radius 2\n-2,-1 #\n"), "line 2: (-2, -1) is outside of the radius 2");
+    }
+
+    #[test]
+    fn rejects_unknown_aspects() {
+        assert_eq!(parse_error("radius 2\n0,0 ordo\n1,1 xyz\n"), "line 3: aspect 'xyz' does not exist");
+        assert_eq!(parse_error("radius 1\n0,0 desiderium\n"), "line 2: Desiderium is not an aspect of ThaumCraft 4");
+        assert_eq!(parse_error("radius 2\n1,0\n"), "line 2: expected an aspect or '#' after 1,0");
+    }
+
+    #[test]
+    fn rejects_a_hex_given_twice() {
+        assert_eq!(parse_error("radius 2\n\n# the center\n0,0 ordo\n0,0 #\n"), "line 5: (0, 0) is given twice");
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use crate::{
+    aspect::Aspect,
+    hex::HexCoord,
+    note::{Hex, ResearchNote},
+    solver::Solver,
+    steiner::{NodeWeightedGraph, SteinerSolver},
+};
+
+/// One aspect to put on an empty hex of the note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub hex: HexCoord,
+    pub aspect: Aspect,
+}
+
+#[derive(Debug, Clone)]
+pub struct NotePlan {
+    /// Ordered outwards from the first fixed aspect, every placement touches an aspect placed before it
+    pub placements: Vec<Placement>,
+    pub price: u32,
+    /// Whether the plan is proven cheapest, false when the greedy fallback was needed
+    pub exact: bool,
+}
+
+/// Why [`PlacementSolver::solve`] returned no plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoPlan {
+    /// The fixed aspects cannot be connected even when hexes may hold several aspects
+    Impossible,
+    /// The cheapest connection puts several aspects on a hex and the greedy search found no other one
+    NotFound,
+}
+
+/// Plans the placements completing a research note, where every fixed aspect has to be connected to the others
+/// through neighbouring hexes holding linked aspects. Solved as a Steiner tree over all usable `(hex, aspect)` pairs,
+/// placing an aspect costing its [`Solver::step_price`] while aspects already on the note are free.
+pub struct PlacementSolver<'a> {
+    note: &'a ResearchNote,
+    nodes: Vec<(HexCoord, Aspect)>,
+    graph: NodeWeightedGraph,
+    terminals: Vec<usize>,
+}
+
+impl<'a> PlacementSolver<'a> {
+    pub fn new(solver: &Solver, note: &'a ResearchNote) -> Result<Self, String> {
+        let mut nodes = Vec::new();
+        let mut prices = Vec::new();
+        let mut terminals = Vec::new();
+        for (&hex, &state) in &note.hexes {
+            match state {
+                Hex::Empty => {
+                    for aspect in solver.aspect_inventory().edition().aspects() {
+                        if let Some(price) = solver.step_price(aspect) {
+                            nodes.push((hex, aspect));
+                            prices.push(Some(price));
+                        }
+                    }
+                }
+                Hex::Fixed(aspect) | Hex::Placed(aspect) => {
+                    if matches!(state, Hex::Fixed(_)) {
+                        terminals.push(nodes.len());
+                    }
+                    nodes.push((hex, aspect));
+                    prices.push(Some(0));
+                }
+                Hex::Blocked => {}
+            }
+        }
+        if terminals.len() > SteinerSolver::MAX_TERMINALS {
+            return Err(format!("Research note {} has more than {} fixed aspects", note.key, SteinerSolver::MAX_TERMINALS));
+        }
+
+        let index_of: HashMap<(HexCoord, Aspect), usize> = nodes.iter().enumerate().map(|(index, &node)| (node, index)).collect();
+        let neighbours = nodes
+            .iter()
+            .map(|&(hex, aspect)| {
+                hex.neighbours()
+                    .into_iter()
+                    .flat_map(|neighbour_hex| solver.neighbours(aspect).map(move |linked| (neighbour_hex, linked)))
+                    .filter_map(|node| index_of.get(&node).copied())
+                    .collect()
+            })
+            .collect();
+
+        Ok(PlacementSolver {
+            note,
+            nodes,
+            graph: NodeWeightedGraph { prices, neighbours },
+            terminals,
+        })
+    }
+
+    /// The exact tree may put two aspects on the same hex, a greedy search that never does is used then.
+    pub fn solve(&self) -> Result<NotePlan, NoPlan> {
+        if self.terminals.len() < 2 {
+            return Ok(NotePlan {
+                placements: Vec::new(),
+                price: 0,
+                exact: true,
+            });
+        }
+
+        let edges = self.graph.cheapest_tree(&self.terminals).ok_or(NoPlan::Impossible)?;
+        let mut aspect_of: HashMap<HexCoord, Aspect> = HashMap::new();
+        let mut plan = NotePlan {
+            placements: Vec::new(),
+            price: 0,
+            exact: true,
+        };
+        for (_, child) in edges {
+            let (hex, aspect) = self.nodes[child];
+            if *aspect_of.entry(hex).or_insert(aspect) != aspect {
+                return self.connect_greedily();
+            }
+            if self.note.hexes[&hex] == Hex::Empty {
+                plan.placements.push(Placement { hex, aspect });
+                plan.price += self.graph.prices[child].unwrap_or_default();
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Grows the network greedily from every fixed aspect in turn and keeps the cheapest plan, as an early path
+    /// can block the hexes a later one would need.
+    fn connect_greedily(&self) -> Result<NotePlan, NoPlan> {
+        self.terminals
+            .iter()
+            .filter_map(|&first| self.connect_greedily_from(first))
+            .min_by_key(|plan| plan.price)
+            .ok_or(NoPlan::NotFound)
+    }
+
+    /// Connects the closest remaining fixed aspect to the network until all are connected, each time with Dijkstra
+    /// from the whole network where hexes taken earlier only allow the aspect already on them and a path never
+    /// comes back to a hex it already crossed.
+    fn connect_greedily_from(&self, first: usize) -> Option<NotePlan> {
+        let mut aspect_of: HashMap<HexCoord, Aspect> = HashMap::new();
+        let mut connected = HashSet::from([first]);
+        let mut plan = NotePlan {
+            placements: Vec::new(),
+            price: 0,
+            exact: false,
+        };
+
+        while self.terminals.iter().any(|terminal| !connected.contains(terminal)) {
+            let mut costs = vec![u32::MAX; self.nodes.len()];
+            let mut previous = vec![None; self.nodes.len()];
+            let mut queue = BinaryHeap::new();
+            for &node in &connected {
+                costs[node] = 0;
+                queue.push(Reverse((0, node)));
+            }
+            while let Some(Reverse((cost, node))) = queue.pop() {
+                if cost > costs[node] {
+                    continue;
+                }
+                for &neighbour in &self.graph.neighbours[node] {
+                    let (hex, aspect) = self.nodes[neighbour];
+                    let price = match aspect_of.get(&hex) {
+                        Some(&taken) if taken == aspect => 0,
+                        Some(_) => continue,
+                        None if self.path_crosses(&previous, node, hex) => continue,
+                        None => self.graph.prices[neighbour].unwrap_or_default(),
+                    };
+                    if cost + price < costs[neighbour] {
+                        costs[neighbour] = cost + price;
+                        previous[neighbour] = Some(node);
+                        queue.push(Reverse((cost + price, neighbour)));
+                    }
+                }
+            }
+
+            let target = self
+                .terminals
+                .iter()
+                .copied()
+                .filter(|terminal| !connected.contains(terminal))
+                .min_by_key(|&terminal| costs[terminal])?;
+            if costs[target] == u32::MAX {
+                return None;
+            }
+
+            let mut path = Vec::new();
+            let mut node = target;
+            while !connected.contains(&node) {
+                path.push(node);
+                node = previous[node]?;
+            }
+            for node in path.into_iter().rev() {
+                let (hex, aspect) = self.nodes[node];
+                if aspect_of.insert(hex, aspect).is_none() && self.note.hexes[&hex] == Hex::Empty {
+                    plan.placements.push(Placement { hex, aspect });
+                    plan.price += self.graph.prices[node].unwrap_or_default();
+                }
+                connected.insert(node);
+            }
+        }
+
+        Some(plan)
+    }
+
+    /// Whether the path Dijkstra found up to `node` already goes through the hex. Nodes are final once popped,
+    /// so the path stays the one checked here.
+    fn path_crosses(&self, previous: &[Option<usize>], node: usize, hex: HexCoord) -> bool {
+        let mut node = Some(node);
+        while let Some(current) = node {
+            if self.nodes[current].0 == hex {
+                return true;
+            }
+            node = previous[current];
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aspect::{AspectInventory, Edition},
+        reserve::Reserves,
+        skill::ResearchSkill,
+    };
+
+    const CORNERS: &str = "radius 4\n0,-4 lux\n4,-4 ignis\n4,0 aqua\n0,4 terra\n-4,4 ordo\n-4,0 perditio\n0,0 #\n1,0 #\n-1,1 #\n";
+
+    /// Checks that a plan only fills empty hexes, one aspect each, every one linked to an aspect already there.
+    fn assert_valid(solver: &Solver, note: &ResearchNote, plan: &NotePlan) {
+        let mut aspect_of: HashMap<HexCoord, Aspect> = note
+            .hexes
+            .iter()
+            .filter_map(|(&hex, &state)| match state {
+                Hex::Fixed(aspect) | Hex::Placed(aspect) => Some((hex, aspect)),
+                _ => None,
+            })
+            .collect();
+        for placement in &plan.placements {
+            assert_eq!(note.hexes[&placement.hex], Hex::Empty, "{:?}", placement);
+            assert!(
+                placement
+                    .hex
+                    .neighbours()
+                    .iter()
+                    .any(|hex| aspect_of.get(hex).is_some_and(|&aspect| solver.neighbours(aspect).any(|linked| linked == placement.aspect))),
+                "{:?}",
+                placement
+            );
+            assert_eq!(aspect_of.insert(placement.hex, placement.aspect), None, "{:?}", placement);
+        }
+        let price: u32 = plan.placements.iter().map(|placement| solver.step_price(placement.aspect).unwrap()).sum();
+        assert_eq!(plan.price, price);
+    }
+
+    #[test]
+    fn greedy_plans_use_every_hex_once() {
+        let note = ResearchNote::parse("CORNERS", CORNERS).unwrap();
+        let solver = Solver::new(AspectInventory::uniform(Edition::Tc4), Reserves::default(), ResearchSkill::None);
+        let placement_solver = PlacementSolver::new(&solver, &note).unwrap();
+
+        let plan = placement_solver.connect_greedily().unwrap();
+        assert!(!plan.exact);
+        assert_valid(&solver, &note, &plan);
+        let plan = placement_solver.solve().unwrap();
+        assert_valid(&solver, &note, &plan);
+    }
+
+    #[test]
+    fn unconnectable_notes_are_impossible() {
+        let note = ResearchNote::parse("PRIMALS", "radius 2\n0,-2 aer\n0,2 terra\n").unwrap();
+        let primals = Edition::Tc4.aspects().into_iter().filter(Aspect::is_primal).map(|aspect| (aspect, 10)).collect();
+        let solver = Solver::new(AspectInventory::new(primals, Edition::Tc4), Reserves::default(), ResearchSkill::None);
+
+        assert_eq!(PlacementSolver::new(&solver, &note).unwrap().solve().unwrap_err(), NoPlan::Impossible);
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use nbt::{Blob, Value};
+use serde::Deserialize;
+
+use crate::aspect::{AspectInventory, Edition};
+
+#[derive(Deserialize)]
+struct UserCacheEntry {
+    name: String,
+    uuid: String,
+}
+
+pub struct PlayerData {
+    pub name: String,
+    pub aspect_inventory: AspectInventory,
+    pub completed_research: HashSet<String>,
+}
+
+impl PlayerData {
+    pub fn from_thaum(name: String, content: &[u8]) -> Result<PlayerData, String> {
+        let blob = Blob::from_gzip_reader(&mut &content[..]).map_err(|error| format!("Thaum file of {} is not valid NBT: {}", name, error))?;
+        let completed_research = PlayerData::parse_completed_research(&blob)?;
+        let aspect_inventory = AspectInventory::from_nbt(blob)?;
+
+        Ok(PlayerData {
+            name,
+            aspect_inventory,
+            completed_research,
+        })
+    }
+
+    /// Reads the research known to the player from the ThaumCraft 6 knowledge capability of a `<uuid>.dat` file.
+    /// ThaumCraft 6 has no per-player aspect pool, so every TC6 aspect counts as discovered and equally available.
+    pub fn from_tc6_dat(name: String, content: &[u8]) -> Result<PlayerData, String> {
+        let blob = Blob::from_gzip_reader(&mut &content[..]).map_err(|error| format!("Player data of {} is not valid NBT: {}", name, error))?;
+        let knowledge = match blob.get("ForgeCaps") {
+            Some(Value::Compound(capabilities)) => match capabilities.get("thaumcraft:knowledge") {
+                Some(Value::Compound(knowledge)) => knowledge,
+                _ => return Err(format!("Player data of {} has no ThaumCraft 6 knowledge, is this a 1.12 server?", name)),
+            },
+            _ => return Err(format!("Player data of {} has no Forge capabilities, is this a 1.12 server?", name)),
+        };
+
+        let completed_research = match knowledge.get("research") {
+            Some(Value::List(research)) => PlayerData::research_keys(research)?,
+            None => HashSet::new(),
+            _ => return Err("The ThaumCraft 6 knowledge does not contain a valid list of research".to_string()),
+        };
+
+        Ok(PlayerData {
+            name,
+            aspect_inventory: AspectInventory::uniform(Edition::Tc6),
+            completed_research,
+        })
+    }
+
+    /// Every username in the server's `usercache.json`, sorted.
+    pub fn usernames_from_user_cache(user_cache: &[u8]) -> Result<Vec<String>, String> {
+        let entries: Vec<UserCacheEntry> = serde_json::from_slice(user_cache).map_err(|error| format!("usercache.json is not valid: {}", error))?;
+        let mut usernames: Vec<String> = entries.into_iter().map(|entry| entry.name).collect();
+        usernames.sort();
+
+        Ok(usernames)
+    }
+
+    /// Looks the username up in the server's `usercache.json`, ignoring case like MineCraft does.
+    pub fn uuid_from_user_cache(user_cache: &[u8], username: &str) -> Result<String, String> {
+        let entries: Vec<UserCacheEntry> = serde_json::from_slice(user_cache).map_err(|error| format!("usercache.json is not valid: {}", error))?;
+        entries
+            .into_iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(username))
+            .map(|entry| entry.uuid)
+            .ok_or_else(|| format!("{} is not in usercache.json, give their UUID instead", username))
+    }
+
+    fn parse_completed_research(nbt: &Blob) -> Result<HashSet<String>, String> {
+        match nbt.get("THAUMCRAFT.RESEARCH") {
+            Some(Value::List(research)) => PlayerData::research_keys(research),
+            None => Ok(HashSet::new()),
+            _ => Err("The NBT structure does not contain a valid list of ThaumCraft research".to_string()),
+        }
+    }
+
+    /// Both editions store research as a list of compounds with a `key` string.
+    fn research_keys(research_values: &[Value]) -> Result<HashSet<String>, String> {
+        research_values
+            .iter()
+            .map(|research| match research {
+                Value::Compound(research_data) => match research_data.get("key") {
+                    Some(Value::String(key)) => Ok(key.clone()),
+                    _ => Err("Research key is missing or not a string".to_string()),
+                },
+                _ => Err("Research list contains unexpected NBT element".to_string()),
+            })
+            .collect()
+    }
+}
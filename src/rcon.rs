@@ -0,0 +1,224 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::source::{self, SourceError};
+
+/// Connection settings of the MineCraft server's RCON console.
+#[derive(Debug, Clone)]
+pub struct RconConfig {
+    pub address: String,
+    pub password: String,
+    pub timeout: Duration,
+}
+
+/// Minimal client for the Source RCON protocol spoken by MineCraft servers with `enable-rcon=true`.
+/// Every packet is `length, request id, type, payload, two nul bytes`, integers in little endian.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    pub const DEFAULT_PORT: u16 = 25575;
+
+    const TYPE_RESPONSE: i32 = 0;
+    const TYPE_COMMAND: i32 = 2;
+    const TYPE_LOGIN: i32 = 3;
+    const AUTH_FAILED_ID: i32 = -1;
+    const MAX_PACKET_LENGTH: i32 = 4096 + 10;
+
+    /// Connects and logs in, failing with an authentication error when the server rejects the password.
+    pub fn connect(config: &RconConfig) -> Result<Self, SourceError> {
+        let address = &config.address;
+        let (host, port) = source::host_and_port(address, RconClient::DEFAULT_PORT)?;
+        let socket_address = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|error| SourceError::Network(format!("Could not resolve '{}': {}", address, error)))?
+            .next()
+            .ok_or_else(|| SourceError::Network(format!("Could not resolve '{}'", address)))?;
+        let stream = TcpStream::connect_timeout(&socket_address, config.timeout)
+            .and_then(|stream| stream.set_read_timeout(Some(config.timeout)).map(|_| stream))
+            .and_then(|stream| stream.set_write_timeout(Some(config.timeout)).map(|_| stream))
+            .map_err(|error| RconClient::classify("Could not connect to RCON", error))?;
+
+        let mut client = RconClient { stream, next_id: 1 };
+        let login_id = client.send(RconClient::TYPE_LOGIN, &config.password)?;
+        loop {
+            // Some servers send an empty response before the actual login result.
+            let (id, kind, _) = client.receive()?;
+            if kind == RconClient::TYPE_RESPONSE {
+                continue;
+            }
+            if id == RconClient::AUTH_FAILED_ID {
+                return Err(SourceError::Auth("RCON password was rejected".to_string()));
+            }
+            if id == login_id {
+                return Ok(client);
+            }
+        }
+    }
+
+    /// Runs a console command and returns its output. The server only answers once the command has finished.
+    pub fn command(&mut self, command: &str) -> Result<String, SourceError> {
+        let command_id = self.send(RconClient::TYPE_COMMAND, command)?;
+        loop {
+            let (id, _, payload) = self.receive()?;
+            if id == command_id {
+                return Ok(payload);
+            }
+        }
+    }
+
+    fn send(&mut self, kind: i32, payload: &str) -> Result<i32, SourceError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut packet = Vec::with_capacity(payload.len() + 14);
+        packet.extend_from_slice(&(payload.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(payload.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&packet).map_err(|error| RconClient::classify("Could not send RCON packet", error))?;
+
+        Ok(id)
+    }
+
+    fn receive(&mut self) -> Result<(i32, i32, String), SourceError> {
+        let length = i32::from_le_bytes(self.read_array()?);
+        if !(10..=RconClient::MAX_PACKET_LENGTH).contains(&length) {
+            return Err(SourceError::Other(format!("Invalid RCON packet length {}", length)));
+        }
+
+        let id = i32::from_le_bytes(self.read_array()?);
+        let kind = i32::from_le_bytes(self.read_array()?);
+        let mut body = vec![0; length as usize - 8];
+        self.stream
+            .read_exact(&mut body)
+            .map_err(|error| RconClient::classify("Could not receive RCON packet", error))?;
+        let payload = String::from_utf8_lossy(&body[..body.len() - 2]).into_owned();
+
+        Ok((id, kind, payload))
+    }
+
+    fn read_array(&mut self) -> Result<[u8; 4], SourceError> {
+        let mut bytes = [0; 4];
+        self.stream
+            .read_exact(&mut bytes)
+            .map_err(|error| RconClient::classify("Could not receive RCON packet", error))?;
+
+        Ok(bytes)
+    }
+
+    fn classify(context: &str, error: io::Error) -> SourceError {
+        match error.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => SourceError::Network(format!("{}: no response within the timeout", context)),
+            _ => SourceError::Network(format!("{}: {}", context, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    const PASSWORD: &str = "secret";
+
+    fn read_packet(stream: &mut TcpStream) -> Option<(i32, i32, String)> {
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).ok()?;
+        let mut body = vec![0; i32::from_le_bytes(length) as usize];
+        stream.read_exact(&mut body).ok()?;
+        let id = i32::from_le_bytes(body[0..4].try_into().unwrap());
+        let kind = i32::from_le_bytes(body[4..8].try_into().unwrap());
+
+        Some((id, kind, String::from_utf8_lossy(&body[8..body.len() - 2]).into_owned()))
+    }
+
+    fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, payload: &str) {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&(payload.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(payload.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).unwrap();
+    }
+
+    /// Serves one connection like a MineCraft server: an empty response before the login result,
+    /// the type 2 login result with the request id or -1, then one response per command.
+    fn fake_server() -> String {
+        fake_server_on(TcpListener::bind("127.0.0.1:0").unwrap())
+    }
+
+    fn fake_server_on(listener: TcpListener) -> String {
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            while let Some((id, kind, payload)) = read_packet(&mut stream) {
+                match kind {
+                    RconClient::TYPE_LOGIN => {
+                        write_packet(&mut stream, id, RconClient::TYPE_RESPONSE, "");
+                        let login_id = if payload == PASSWORD { id } else { RconClient::AUTH_FAILED_ID };
+                        write_packet(&mut stream, login_id, RconClient::TYPE_COMMAND, "");
+                    }
+                    RconClient::TYPE_COMMAND if payload == "save-all" => write_packet(&mut stream, id, RconClient::TYPE_RESPONSE, "Saved the game"),
+                    _ => write_packet(&mut stream, id, RconClient::TYPE_RESPONSE, &format!("Unknown command: {}", payload)),
+                }
+            }
+        });
+
+        address
+    }
+
+    fn config(address: String, password: &str) -> RconConfig {
+        RconConfig {
+            address,
+            password: password.to_string(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn commands_return_the_payload_after_login() {
+        let mut client = RconClient::connect(&config(fake_server(), PASSWORD)).unwrap();
+        assert_eq!(client.command("save-all"), Ok("Saved the game".to_string()));
+        assert_eq!(client.command("list"), Ok("Unknown command: list".to_string()));
+    }
+
+    #[test]
+    fn rejected_passwords_are_authentication_errors() {
+        let result = RconClient::connect(&config(fake_server(), "wrong"));
+        assert!(matches!(result, Err(SourceError::Auth(_))));
+    }
+
+    #[test]
+    fn unreachable_servers_are_network_errors() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let result = RconClient::connect(&config(address, PASSWORD));
+        assert!(matches!(result, Err(SourceError::Network(_))));
+    }
+
+    #[test]
+    fn ipv6_addresses_use_the_default_port_when_bare() {
+        // Hosts without IPv6 loopback have nothing to test
+        let Ok(listener) = TcpListener::bind("[::1]:0") else {
+            return;
+        };
+        let address = fake_server_on(listener);
+        assert!(address.starts_with("[::1]:"), "{}", address);
+        let mut client = RconClient::connect(&config(address, PASSWORD)).unwrap();
+        assert_eq!(client.command("save-all"), Ok("Saved the game".to_string()));
+
+        // Nothing listens on the default port, but the address has to resolve to get that far
+        match RconClient::connect(&config("::1".to_string(), PASSWORD)) {
+            Err(SourceError::Network(message)) => assert!(message.starts_with("Could not connect to RCON"), "{}", message),
+            other => panic!("expected a connection failure, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    cmp::min,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{aspect::Aspect, hex::HexCoord, note::ResearchNote, solver::Solver};
+
+/// Aspects of a dumped research entry, either plain keys or the `tags` with their amounts.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawAspects {
+    Keys(Vec<String>),
+    Amounts(HashMap<String, u32>),
+}
+
+#[derive(Deserialize)]
+struct RawResearchEntry {
+    key: String,
+    category: String,
+    complexity: u8,
+    aspects: RawAspects,
+}
+
+/// A research of the pack together with the aspects its research note starts with.
+#[derive(Debug, Clone)]
+pub struct ResearchEntry {
+    pub key: String,
+    pub category: String,
+    pub complexity: u8,
+    pub aspects: Vec<Aspect>,
+}
+
+impl ResearchEntry {
+    /// ThaumCraft 4 sizes the note by complexity, `1 + min(3, complexity)`.
+    pub fn note_radius(&self) -> i32 {
+        min(1 + self.complexity as i32, ResearchNote::MAX_RADIUS)
+    }
+}
+
+/// Research entries imported from a JSON dump of the pack, a list of objects with `key`, `category`,
+/// `complexity` and `aspects`, the latter either a list of aspect keys or a map of aspect keys to amounts.
+pub struct ResearchCatalog {
+    entries: Vec<ResearchEntry>,
+}
+
+impl ResearchCatalog {
+    const FILE_NAME: &'static str = "research.json";
+
+    /// `<config dir>/thaumcraft-research-solver/research.json`, next to the config file.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(ResearchCatalog::FILE_NAME))
+    }
+
+    pub fn load(path: &Path) -> Result<ResearchCatalog, String> {
+        let content = fs::read(path).map_err(|error| format!("Could not read research catalog '{}': {}", path.display(), error))?;
+        let raw_entries: Vec<RawResearchEntry> = serde_json::from_slice(&content).map_err(|error| format!("Invalid research catalog '{}': {}", path.display(), error))?;
+
+        let mut entries = Vec::with_capacity(raw_entries.len());
+        for raw_entry in raw_entries {
+            let aspect_keys = match raw_entry.aspects {
+                RawAspects::Keys(keys) => keys,
+                RawAspects::Amounts(amounts) => {
+                    // Maps have no order, ThaumCraft places note aspects sorted by key anyway.
+                    let mut keys: Vec<String> = amounts.into_keys().collect();
+                    keys.sort();
+                    keys
+                }
+            };
+
+            let aspects = aspect_keys
+                .iter()
+                .map(|key| Aspect::get_by_key(key).ok_or_else(|| format!("Research {} uses unknown aspect '{}'", raw_entry.key, key)))
+                .collect::<Result<Vec<Aspect>, String>>()?;
+            entries.push(ResearchEntry {
+                key: raw_entry.key,
+                category: raw_entry.category,
+                complexity: raw_entry.complexity,
+                aspects,
+            });
+        }
+        entries.sort_by(|a, b| (&a.category, &a.key).cmp(&(&b.category, &b.key)));
+
+        Ok(ResearchCatalog { entries })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ResearchEntry> {
+        self.entries.iter().find(|entry| entry.key.eq_ignore_ascii_case(key))
+    }
+
+    /// Entries not in the completed research, sorted by category and key.
+    pub fn open_entries(&self, completed_research: &HashSet<String>) -> Vec<&ResearchEntry> {
+        self.entries.iter().filter(|entry| !completed_research.contains(&entry.key)).collect()
+    }
+}
+
+/// One connection the note needs, between two neighbouring starting aspects on the ring.
+#[derive(Debug, Clone)]
+pub struct CostLink {
+    pub from: Aspect,
+    pub to: Aspect,
+    /// Hex distance between the two starting aspects
+    pub distance: i32,
+    /// Path length and price of the cheapest path, `None` if they cannot be connected
+    pub cheapest: Option<(u8, u32)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CostEstimate {
+    pub radius: i32,
+    pub links: Vec<CostLink>,
+}
+
+impl CostEstimate {
+    /// Spreads the starting aspects evenly around the outer ring like ThaumCraft does, then prices
+    /// a path between each pair of neighbours. The real note places them randomly, so this is only an estimate.
+    pub fn estimate(entry: &ResearchEntry, solver: &Solver) -> CostEstimate {
+        let radius = entry.note_radius();
+        let ring = HexCoord::CENTER.ring(radius);
+        let positions: Vec<HexCoord> = (0..entry.aspects.len()).map(|index| ring[index * ring.len() / entry.aspects.len()]).collect();
+
+        let links = entry
+            .aspects
+            .windows(2)
+            .zip(positions.windows(2))
+            .map(|(aspects, positions)| {
+                let distance = positions[0].distance(positions[1]);
+                let length = (distance + 1) as u8;
+                CostLink {
+                    from: aspects[0],
+                    to: aspects[1],
+                    distance,
+                    cheapest: solver.find_cheapest_price(aspects[0], aspects[1], length, min(12 - length, 3)),
+                }
+            })
+            .collect();
+
+        CostEstimate { radius, links }
+    }
+
+    /// Sum of all link prices, `None` if any pair cannot be connected.
+    pub fn total_price(&self) -> Option<u32> {
+        self.links.iter().map(|link| link.cheapest.map(|(_, price)| price)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::{
+        aspect::{AspectInventory, Edition},
+        reserve::Reserves,
+        skill::ResearchSkill,
+    };
+
+    fn load_catalog(name: &str, content: &str) -> Result<ResearchCatalog, String> {
+        let path = std::env::temp_dir().join(format!("{}-{}-research-{}.json", env!("CARGO_PKG_NAME"), process::id(), name));
+        fs::write(&path, content).unwrap();
+        let catalog = ResearchCatalog::load(&path);
+        fs::remove_file(&path).unwrap();
+        catalog
+    }
+
+    fn entry(complexity: u8, aspects: &[Aspect]) -> ResearchEntry {
+        ResearchEntry {
+            key: "TEST".to_string(),
+            category: "BASICS".to_string(),
+            complexity,
+            aspects: aspects.to_vec(),
+        }
+    }
+
+    #[test]
+    fn catalogs_accept_aspect_lists_maps_and_extra_fields() {
+        let catalog = load_catalog(
+            "shapes",
+            r#"[
+                {"key": "GOGGLES", "category": "ARTIFICE", "complexity": 2, "aspects": ["sensus", "aer"], "parents": ["THAUMOMETER"]},
+                {"key": "ALUMENTUM", "category": "ALCHEMY", "complexity": 1, "aspects": {"potentia": 2, "ignis": 1, "perditio": 3}, "page": 4}
+            ]"#,
+        )
+        .unwrap();
+
+        let keys: Vec<&str> = catalog.entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, ["ALUMENTUM", "GOGGLES"]);
+        assert_eq!(catalog.get("goggles").unwrap().aspects, [Aspect::Sensus, Aspect::Aer]);
+        assert_eq!(catalog.get("ALUMENTUM").unwrap().aspects, [Aspect::Ignis, Aspect::Perditio, Aspect::Potentia]);
+
+        let completed = HashSet::from(["GOGGLES".to_string()]);
+        let open: Vec<&str> = catalog.open_entries(&completed).iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(open, ["ALUMENTUM"]);
+    }
+
+    #[test]
+    fn catalogs_reject_unknown_aspects_and_missing_fields() {
+        let error = load_catalog("unknown", r#"[{"key": "X", "category": "BASICS", "complexity": 1, "aspects": ["aer", "nothing"]}]"#)
+            .err()
+            .unwrap();
+        assert_eq!(error, "Research X uses unknown aspect 'nothing'");
+
+        let error = load_catalog("missing", r#"[{"key": "X", "category": "BASICS", "aspects": ["aer"]}]"#).err().unwrap();
+        assert!(error.starts_with("Invalid research catalog"), "{}", error);
+    }
+
+    #[test]
+    fn note_radius_grows_with_complexity_up_to_the_largest_note() {
+        assert_eq!(entry(0, &[]).note_radius(), 1);
+        assert_eq!(entry(1, &[]).note_radius(), 2);
+        assert_eq!(entry(3, &[]).note_radius(), ResearchNote::MAX_RADIUS);
+        assert_eq!(entry(9, &[]).note_radius(), ResearchNote::MAX_RADIUS);
+    }
+
+    #[test]
+    fn estimates_chain_neighbours_around_the_ring() {
+        let solver = Solver::new(AspectInventory::uniform(Edition::Tc4), Reserves::default(), ResearchSkill::None);
+        let entry = entry(2, &[Aspect::Aer, Aspect::Ignis, Aspect::Aqua]);
+        let estimate = CostEstimate::estimate(&entry, &solver);
+        assert_eq!(estimate.radius, 3);
+
+        let ring = HexCoord::CENTER.ring(3);
+        let positions = [ring[0], ring[ring.len() / 3], ring[2 * ring.len() / 3]];
+        let pairs: Vec<(Aspect, Aspect, i32)> = estimate.links.iter().map(|link| (link.from, link.to, link.distance)).collect();
+        assert_eq!(
+            pairs,
+            [
+                (Aspect::Aer, Aspect::Ignis, positions[0].distance(positions[1])),
+                (Aspect::Ignis, Aspect::Aqua, positions[1].distance(positions[2]))
+            ]
+        );
+
+        let mut total = 0;
+        for link in &estimate.links {
+            let length = (link.distance + 1) as u8;
+            let cheapest = link.cheapest.unwrap();
+            assert_eq!(Some(cheapest), solver.find_cheapest_price(link.from, link.to, length, min(12 - length, 3)));
+            assert!(cheapest.0 >= length);
+            total += cheapest.1;
+        }
+        assert_eq!(estimate.total_price(), Some(total));
+
+        let mut unconnectable = estimate.clone();
+        unconnectable.links[1].cheapest = None;
+        assert_eq!(unconnectable.total_price(), None);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+use crate::aspect::{Aspect, AspectInventory};
+
+/// How the solver treats a step that would push an aspect below its reserve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ReservePolicy {
+    /// Never place an aspect that is at or below its reserve
+    Forbid,
+    /// Allow it, but add a price high enough to prefer any other path
+    #[default]
+    Penalize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Reserves {
+    thresholds: HashMap<Aspect, u16>,
+    policy: ReservePolicy,
+}
+
+impl Reserves {
+    pub const PENALTY: u32 = u16::MAX as u32;
+
+    pub fn new(thresholds: HashMap<Aspect, u16>, policy: ReservePolicy) -> Self {
+        Reserves { thresholds, policy }
+    }
+
+    pub fn policy(&self) -> ReservePolicy {
+        self.policy
+    }
+
+    pub fn threshold_of(&self, aspect: Aspect) -> u16 {
+        self.thresholds.get(&aspect).copied().unwrap_or(0)
+    }
+
+    pub fn is_below(&self, aspect_inventory: &AspectInventory, aspect: Aspect) -> bool {
+        aspect_inventory.amount_of(aspect) < self.threshold_of(aspect)
+    }
+
+    /// Returns true if placing one more of the aspect would leave less than its reserve.
+    pub fn is_breached_by_step(&self, aspect_inventory: &AspectInventory, aspect: Aspect) -> bool {
+        let threshold = self.threshold_of(aspect);
+        threshold > 0 && aspect_inventory.amount_of(aspect) <= threshold
+    }
+
+    /// Discovered aspects below their reserve, undiscovered ones are reported separately.
+    pub fn low_stock(&self, aspect_inventory: &AspectInventory) -> Vec<(Aspect, u16, u16)> {
+        aspect_inventory
+            .edition()
+            .aspects()
+            .into_iter()
+            .filter(|&aspect| aspect_inventory.is_discovered(aspect) && self.is_below(aspect_inventory, aspect))
+            .map(|aspect| (aspect, aspect_inventory.amount_of(aspect), self.threshold_of(aspect)))
+            .collect()
+    }
+}
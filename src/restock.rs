@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use crate::{
+    aspect::{Aspect, AspectInventory},
+    reserve::Reserves,
+};
+
+/// A single research-table step combining `count` pairs of components into the composite aspect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Combination {
+    pub composite: Aspect,
+    pub components: (Aspect, Aspect),
+    pub count: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestockPlan {
+    pub combinations: Vec<Combination>,
+    pub unmet_targets: Vec<(Aspect, u16)>,
+}
+
+pub struct RestockPlanner<'a> {
+    aspect_inventory: &'a AspectInventory,
+    reserves: &'a Reserves,
+    targets: HashMap<Aspect, u16>,
+}
+
+impl<'a> RestockPlanner<'a> {
+    pub fn new(aspect_inventory: &'a AspectInventory, reserves: &'a Reserves, targets: HashMap<Aspect, u16>) -> Self {
+        RestockPlanner {
+            aspect_inventory,
+            reserves,
+            targets,
+        }
+    }
+
+    /// Plans combinations one at a time, always picking the deficit whose crafting chain draws on
+    /// the most plentiful components, until every target is met or nothing more can be crafted.
+    pub fn plan(&self) -> RestockPlan {
+        let aspects = self.aspect_inventory.edition().aspects();
+        let mut stock: HashMap<Aspect, u16> = aspects.iter().map(|&aspect| (aspect, self.aspect_inventory.amount_of(aspect))).collect();
+        let mut plan = RestockPlan::default();
+
+        loop {
+            let best_chain = aspects
+                .iter()
+                .filter(|&&aspect| stock[&aspect] < self.target_of(aspect))
+                .filter_map(|&aspect| {
+                    let mut chain_stock = stock.clone();
+                    let mut chain = Vec::new();
+                    let surplus = self.craft(aspect, &mut chain_stock, &mut chain)?;
+                    Some((surplus, chain, chain_stock))
+                })
+                .max_by_key(|(surplus, _, _)| *surplus);
+
+            match best_chain {
+                Some((_, chain, chain_stock)) => {
+                    stock = chain_stock;
+                    for composite in chain {
+                        self.push_combination(&mut plan.combinations, composite);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        plan.unmet_targets = aspects
+            .iter()
+            .filter(|&&aspect| stock[&aspect] < self.target_of(aspect))
+            .map(|&aspect| (aspect, self.target_of(aspect) - stock[&aspect]))
+            .collect();
+
+        plan
+    }
+
+    fn target_of(&self, aspect: Aspect) -> u16 {
+        self.targets.get(&aspect).copied().unwrap_or(0)
+    }
+
+    /// Components are never consumed below their own target or reserve.
+    fn floor_of(&self, aspect: Aspect) -> u16 {
+        self.target_of(aspect).max(self.reserves.threshold_of(aspect))
+    }
+
+    /// Crafts one unit of the aspect, crafting missing components recursively. Returns the smallest
+    /// surplus of any component consumed along the way, or `None` if the aspect cannot be crafted.
+    fn craft(&self, aspect: Aspect, stock: &mut HashMap<Aspect, u16>, chain: &mut Vec<Aspect>) -> Option<u16> {
+        let (component_a, component_b) = aspect.components(self.aspect_inventory.edition())?;
+
+        let mut lowest_surplus = u16::MAX;
+        for component in [component_a, component_b] {
+            let surplus = stock[&component].saturating_sub(self.floor_of(component));
+            let surplus = if surplus > 0 { surplus } else { self.craft(component, stock, chain)? };
+            lowest_surplus = lowest_surplus.min(surplus);
+
+            let amount = stock.get_mut(&component)?;
+            *amount = amount.checked_sub(1)?;
+        }
+
+        *stock.get_mut(&aspect)? += 1;
+        chain.push(aspect);
+
+        Some(lowest_surplus)
+    }
+
+    fn push_combination(&self, combinations: &mut Vec<Combination>, composite: Aspect) {
+        if let Some(last) = combinations.last_mut() {
+            if last.composite == composite {
+                last.count += 1;
+                return;
+            }
+        }
+
+        if let Some(components) = composite.components(self.aspect_inventory.edition()) {
+            combinations.push(Combination { composite, components, count: 1 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aspect::Edition, reserve::ReservePolicy};
+
+    fn plan_for(amounts: &[(Aspect, u16)], thresholds: &[(Aspect, u16)], targets: &[(Aspect, u16)]) -> RestockPlan {
+        let aspect_inventory = AspectInventory::new(amounts.iter().copied().collect(), Edition::Tc4);
+        let reserves = Reserves::new(thresholds.iter().copied().collect(), ReservePolicy::Penalize);
+
+        RestockPlanner::new(&aspect_inventory, &reserves, targets.iter().copied().collect()).plan()
+    }
+
+    fn combination(composite: Aspect, components: (Aspect, Aspect), count: u16) -> Combination {
+        Combination { composite, components, count }
+    }
+
+    #[test]
+    fn crafts_from_the_most_plentiful_components_first() {
+        // Lux can only draw on 2 Ignis while Motus draws on at least 4 Ordo, so all of Motus is crafted first
+        let plan = plan_for(&[(Aspect::Aer, 20), (Aspect::Ignis, 2), (Aspect::Ordo, 8)], &[], &[(Aspect::Lux, 1), (Aspect::Motus, 5)]);
+        assert_eq!(
+            plan.combinations,
+            vec![
+                combination(Aspect::Motus, (Aspect::Aer, Aspect::Ordo), 5),
+                combination(Aspect::Lux, (Aspect::Aer, Aspect::Ignis), 1)
+            ]
+        );
+        assert!(plan.unmet_targets.is_empty());
+    }
+
+    #[test]
+    fn components_stay_above_their_target_and_reserve() {
+        let plan = plan_for(&[(Aspect::Aer, 10), (Aspect::Ignis, 10)], &[(Aspect::Aer, 9)], &[(Aspect::Lux, 3)]);
+        assert_eq!(plan.combinations, vec![combination(Aspect::Lux, (Aspect::Aer, Aspect::Ignis), 1)]);
+        assert_eq!(plan.unmet_targets, vec![(Aspect::Lux, 2)]);
+
+        let plan = plan_for(&[(Aspect::Aer, 10), (Aspect::Ignis, 10)], &[(Aspect::Aer, 2)], &[(Aspect::Lux, 3), (Aspect::Ignis, 8)]);
+        assert_eq!(plan.combinations, vec![combination(Aspect::Lux, (Aspect::Aer, Aspect::Ignis), 2)]);
+        assert_eq!(plan.unmet_targets, vec![(Aspect::Lux, 1)]);
+    }
+
+    #[test]
+    fn leaves_targets_unmet_when_nothing_can_be_crafted() {
+        let plan = plan_for(&[(Aspect::Ignis, 10)], &[], &[(Aspect::Aer, 5), (Aspect::Lux, 2)]);
+        assert!(plan.combinations.is_empty());
+        assert_eq!(plan.unmet_targets, vec![(Aspect::Aer, 5), (Aspect::Lux, 2)]);
+
+        let plan = plan_for(&[(Aspect::Aer, 4)], &[], &[(Aspect::Aer, 3)]);
+        assert!(plan.combinations.is_empty());
+        assert!(plan.unmet_targets.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+
+/// Research skill of a player, giving a chance that placing an aspect on a note does not consume it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ResearchSkill {
+    #[default]
+    None,
+    /// Research Expertise, 10% chance of not consuming the aspect
+    Expertise,
+    /// Research Mastery, 20% chance of not consuming the aspect
+    Mastery,
+}
+
+impl ResearchSkill {
+    const EXPERTISE_KEY: &'static str = "RESEARCHER1";
+    const MASTERY_KEY: &'static str = "RESEARCHER2";
+
+    pub fn from_completed_research(completed_research: &HashSet<String>) -> Self {
+        if completed_research.contains(ResearchSkill::MASTERY_KEY) {
+            ResearchSkill::Mastery
+        } else if completed_research.contains(ResearchSkill::EXPERTISE_KEY) {
+            ResearchSkill::Expertise
+        } else {
+            ResearchSkill::None
+        }
+    }
+
+    /// Chance in percent that a placed aspect is consumed. Every step price is scaled by it, so the skill
+    /// changes expected costs but never which paths are the cheapest.
+    pub fn consume_percent(&self) -> u32 {
+        match self {
+            ResearchSkill::None => 100,
+            ResearchSkill::Expertise => 90,
+            ResearchSkill::Mastery => 80,
+        }
+    }
+
+    pub fn expected_consumption(&self, placed: usize) -> f64 {
+        placed as f64 * self.consume_percent() as f64 / 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn research(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_the_highest_skill_completed() {
+        assert_eq!(ResearchSkill::from_completed_research(&research(&[])), ResearchSkill::None);
+        assert_eq!(ResearchSkill::from_completed_research(&research(&["ALUMENTUM", "researcher1"])), ResearchSkill::None);
+        assert_eq!(ResearchSkill::from_completed_research(&research(&["ALUMENTUM", "RESEARCHER1"])), ResearchSkill::Expertise);
+        assert_eq!(ResearchSkill::from_completed_research(&research(&["RESEARCHER2"])), ResearchSkill::Mastery);
+        assert_eq!(ResearchSkill::from_completed_research(&research(&["RESEARCHER1", "RESEARCHER2"])), ResearchSkill::Mastery);
+    }
+
+    #[test]
+    fn expects_fewer_aspects_consumed_with_more_skill() {
+        assert_eq!(ResearchSkill::None.expected_consumption(5), 5.0);
+        assert_eq!(ResearchSkill::Expertise.expected_consumption(5), 4.5);
+        assert_eq!(ResearchSkill::Mastery.expected_consumption(5), 4.0);
+    }
+}
@@ -1,151 +1,216 @@
-use std::collections::{HashMap, VecDeque};
+use std::{collections::HashMap, path::Path};
 
 use crate::{
-    aspect::{Aspect, AspectInventory},
+    aspect::{Aspect, AspectInventory, Edition},
     graph::Graph,
+    reserve::{ReservePolicy, Reserves},
+    skill::ResearchSkill,
+    table::CostTable,
 };
 
-struct SolverState {
-    node: Aspect,
-    price: u32,
-    distance: u8,
-    path: Vec<Aspect>,
-}
-
 pub struct Solver {
     aspect_graph: Graph<Aspect>,
     aspect_inventory: AspectInventory,
+    reserves: Reserves,
+    research_skill: ResearchSkill,
+    cost_table: Option<CostTable>,
 }
 
 impl Solver {
-    pub fn new(aspect_inventory: AspectInventory) -> Self {
+    /// Prices are expected costs, kept as integers in hundredths of the base aspect price.
+    pub const PRICE_SCALE: u32 = 100;
+
+    pub fn new(aspect_inventory: AspectInventory, reserves: Reserves, research_skill: ResearchSkill) -> Self {
         Solver {
-            aspect_graph: Solver::build_aspect_graph(),
+            aspect_graph: Solver::build_aspect_graph(aspect_inventory.edition()),
             aspect_inventory,
+            reserves,
+            research_skill,
+            cost_table: None,
         }
     }
 
-    pub fn find_paths(&self, start: Aspect, end: Aspect, distance: u8, max_distance_increase: u8) -> HashMap<u8, AspectPaths> {
-        let mut best_paths = HashMap::new();
-        for increase in 0..max_distance_increase {
-            let paths = self.find_paths_with_length(start, end, distance + increase);
-            best_paths.insert(increase, paths);
+    pub fn format_price(price: u32) -> String {
+        format!("{:.2}", price as f64 / Solver::PRICE_SCALE as f64)
+    }
+
+    pub fn aspect_inventory(&self) -> &AspectInventory {
+        &self.aspect_inventory
+    }
+
+    /// Replaces the inventory, e.g. after the player's `.thaum` file changed on the server.
+    /// A precomputed cost table is rebuilt in memory for the new inventory.
+    pub fn set_aspect_inventory(&mut self, aspect_inventory: AspectInventory) {
+        self.aspect_inventory = aspect_inventory;
+        if self.cost_table.is_some() {
+            self.cost_table = Some(CostTable::build(self));
         }
+    }
 
-        best_paths
+    pub fn reserves(&self) -> &Reserves {
+        &self.reserves
     }
 
-    fn find_paths_with_length(&self, start: Aspect, end: Aspect, desired_distance: u8) -> AspectPaths {
-        let mut queue = VecDeque::new();
+    pub fn cost_table(&self) -> Option<&CostTable> {
+        self.cost_table.as_ref()
+    }
 
-        let mut lowest_price = u32::MAX;
-        let mut paths = Vec::new();
-        queue.push_back(SolverState {
-            node: start,
-            price: 0,
-            distance: 1,
-            path: vec![start],
+    /// Loads the cost table of this inventory from the cache directory, or builds and stores it there.
+    pub fn precompute_costs(&mut self, cache_dir: Option<&Path>) {
+        self.cost_table = Some(match cache_dir {
+            Some(cache_dir) => CostTable::load_or_build(self, cache_dir),
+            None => CostTable::build(self),
         });
+    }
 
-        let end_price = self.aspect_inventory.price_of(end) as u32;
-        while let Some(SolverState { node, price, distance, path }) = queue.pop_front() {
-            if distance == desired_distance {
-                if node == end {
-                    let price = price - end_price;
-                    if price <= lowest_price {
-                        if price < lowest_price {
-                            paths.clear();
-                            lowest_price = price;
-                        }
-                        paths.push(path.clone());
-                    }
-                }
-                continue;
-            }
-
-            for neighbor in self.aspect_graph.neighbours_cloned_iter(node) {
-                let neighbor_price: u32 = self.aspect_inventory.price_of(neighbor).into();
-                let new_price = neighbor_price + price;
-                if new_price <= lowest_price {
-                    let mut new_path = path.clone();
-                    new_path.push(neighbor);
-                    queue.push_back(SolverState {
-                        node: neighbor,
-                        price: new_price,
-                        distance: distance + 1,
-                        path: new_path,
-                    });
+    pub fn research_skill(&self) -> ResearchSkill {
+        self.research_skill
+    }
+
+    /// Expected price of placing one aspect on the note, or `None` if the step is not allowed at all.
+    /// Undiscovered aspects cannot be placed, while discovered ones out of stock can still be combined.
+    pub fn step_price(&self, aspect: Aspect) -> Option<u32> {
+        if !self.aspect_inventory.is_discovered(aspect) {
+            return None;
+        }
+
+        let mut price: u32 = self.aspect_inventory.price_of(aspect).into();
+        if self.reserves.is_breached_by_step(&self.aspect_inventory, aspect) {
+            match self.reserves.policy() {
+                ReservePolicy::Forbid => return None,
+                ReservePolicy::Penalize => price += Reserves::PENALTY,
+            }
+        }
+
+        Some(price * self.research_skill.consume_percent())
+    }
+
+    /// Cheapest path length and price between `distance` and `distance + max_distance_increase` (exclusive),
+    /// looked up in the cost table when one was precomputed.
+    pub fn find_cheapest_price(&self, start: Aspect, end: Aspect, distance: u8, max_distance_increase: u8) -> Option<(u8, u32)> {
+        let lengths = distance..distance + max_distance_increase;
+        match &self.cost_table {
+            Some(cost_table) if lengths.end <= CostTable::MAX_LENGTH + 1 => lengths
+                .filter_map(|length| cost_table.price(start, end, length).map(|price| (length, price)))
+                .min_by_key(|&(length, price)| (price, length)),
+            _ => {
+                let path_table = self.find_paths(start, (distance + max_distance_increase).saturating_sub(1));
+                lengths
+                    .filter_map(|length| path_table.price(end, length).map(|price| (length, price)))
+                    .min_by_key(|&(length, price)| (price, length))
+            }
+        }
+    }
+
+    /// Aspects that can be placed next to the given one.
+    pub fn neighbours(&self, aspect: Aspect) -> impl Iterator<Item = Aspect> + '_ {
+        self.aspect_graph.neighbours_cloned_iter(aspect)
+    }
+
+    /// Price and number of the cheapest paths of one length, from the cost table when one was precomputed.
+    pub fn price_and_count(&self, start: Aspect, end: Aspect, length: u8) -> Option<(u32, u64)> {
+        match &self.cost_table {
+            Some(cost_table) if length <= CostTable::MAX_LENGTH => cost_table.price(start, end, length).map(|price| (price, cost_table.count(start, end, length))),
+            _ => {
+                let path_table = self.find_paths(start, length);
+                path_table.price(end, length).map(|price| (price, path_table.count(end, length)))
+            }
+        }
+    }
+
+    /// Computes the cheapest walks from `start` for every length up to `max_length` in a single pass,
+    /// layer by layer, keeping only the minimal price and its predecessors for each aspect and step.
+    pub fn find_paths(&self, start: Aspect, max_length: u8) -> PathTable<'_> {
+        let mut layers = vec![HashMap::from([(
+            start,
+            PathStep {
+                price: 0,
+                predecessors: Vec::new(),
+                count: 1,
+            },
+        )])];
+        // The end aspect is already on the note, so only the steps before it cost anything
+        // and the last layer needed is the one right before the end of the longest path.
+        for _ in 2..max_length {
+            let mut next_layer: HashMap<Aspect, PathStep> = HashMap::new();
+            for (&aspect, step) in layers.last().unwrap() {
+                for neighbour in self.aspect_graph.neighbours_cloned_iter(aspect) {
+                    let Some(step_price) = self.step_price(neighbour) else {
+                        continue;
+                    };
+                    PathStep::relax(&mut next_layer, neighbour, step.price + step_price, aspect, step.count);
                 }
             }
+            layers.push(next_layer);
         }
 
-        AspectPaths::new(paths, lowest_price)
+        PathTable {
+            aspect_graph: &self.aspect_graph,
+            layers,
+        }
     }
 
-    fn add_composite_edges(graph: &mut Graph<Aspect>, composite: Aspect, primal_a: Aspect, primal_b: Aspect) {
-        graph.add_indirectional_edge(composite, primal_a);
-        graph.add_indirectional_edge(composite, primal_b);
+    /// The `count` cheapest paths with exactly `length` aspects, cheapest first, including more expensive ones
+    /// than the tied minimum. Every aspect keeps its `count` cheapest partial paths per step, each remembering
+    /// which partial path of the previous step it extends, so the best paths are read back from the end.
+    pub fn find_best_paths(&self, start: Aspect, end: Aspect, length: u8, count: usize) -> Vec<(u32, AspectPath)> {
+        if length < 2 || count == 0 {
+            return Vec::new();
+        }
+
+        let mut layers = vec![HashMap::from([(start, vec![RankedStep { price: 0, predecessor: None }])])];
+        for index in 1..length {
+            let is_end = index == length - 1;
+            let mut next_layer: HashMap<Aspect, Vec<RankedStep>> = HashMap::new();
+            for (&aspect, steps) in layers.last().unwrap() {
+                for neighbour in self.aspect_graph.neighbours_cloned_iter(aspect) {
+                    // The end aspect is already on the note, so only the intermediate steps cost anything
+                    let step_price = match (is_end, self.step_price(neighbour)) {
+                        (true, _) if neighbour == end => 0,
+                        (false, Some(step_price)) => step_price,
+                        _ => continue,
+                    };
+                    let candidates = next_layer.entry(neighbour).or_default();
+                    candidates.extend(steps.iter().enumerate().map(|(rank, step)| RankedStep {
+                        price: step.price + step_price,
+                        predecessor: Some((aspect, rank)),
+                    }));
+                }
+            }
+            for candidates in next_layer.values_mut() {
+                candidates.sort_by_key(|step| step.price);
+                candidates.truncate(count);
+            }
+            layers.push(next_layer);
+        }
+
+        let Some(end_steps) = layers.last().unwrap().get(&end) else {
+            return Vec::new();
+        };
+        end_steps
+            .iter()
+            .map(|end_step| {
+                let mut path = vec![end];
+                let mut predecessor = end_step.predecessor;
+                while let Some((aspect, rank)) = predecessor {
+                    predecessor = layers[length as usize - 1 - path.len()][&aspect][rank].predecessor;
+                    path.push(aspect);
+                }
+                path.reverse();
+
+                (end_step.price, path)
+            })
+            .collect()
     }
 
-    fn build_aspect_graph() -> Graph<Aspect> {
+    fn build_aspect_graph(edition: Edition) -> Graph<Aspect> {
         let mut graph = Graph::new();
-        Solver::add_composite_edges(&mut graph, Aspect::Alienis, Aspect::Vacuos, Aspect::Tenebrae);
-        Solver::add_composite_edges(&mut graph, Aspect::Arbor, Aspect::Aer, Aspect::Herba);
-        Solver::add_composite_edges(&mut graph, Aspect::Auram, Aspect::Praecantatio, Aspect::Aer);
-        Solver::add_composite_edges(&mut graph, Aspect::Bestia, Aspect::Motus, Aspect::Victus);
-        Solver::add_composite_edges(&mut graph, Aspect::Caelum, Aspect::Vitreus, Aspect::Metallum);
-        Solver::add_composite_edges(&mut graph, Aspect::Cognitio, Aspect::Ignis, Aspect::Spiritus);
-        Solver::add_composite_edges(&mut graph, Aspect::Corpus, Aspect::Mortuus, Aspect::Bestia);
-        Solver::add_composite_edges(&mut graph, Aspect::Desidia, Aspect::Vinculum, Aspect::Spiritus);
-        Solver::add_composite_edges(&mut graph, Aspect::Electrum, Aspect::Potentia, Aspect::Machina);
-        Solver::add_composite_edges(&mut graph, Aspect::Exanimis, Aspect::Motus, Aspect::Mortuus);
-        Solver::add_composite_edges(&mut graph, Aspect::Fabrico, Aspect::Humanus, Aspect::Instrumentum);
-        Solver::add_composite_edges(&mut graph, Aspect::Fames, Aspect::Victus, Aspect::Vacuos);
-        Solver::add_composite_edges(&mut graph, Aspect::Gelum, Aspect::Ignis, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Gula, Aspect::Fames, Aspect::Vacuos);
-        Solver::add_composite_edges(&mut graph, Aspect::Herba, Aspect::Victus, Aspect::Terra);
-        Solver::add_composite_edges(&mut graph, Aspect::Humanus, Aspect::Bestia, Aspect::Cognitio);
-        Solver::add_composite_edges(&mut graph, Aspect::Infernus, Aspect::Ignis, Aspect::Praecantatio);
-        Solver::add_composite_edges(&mut graph, Aspect::Instrumentum, Aspect::Humanus, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Invidia, Aspect::Sensus, Aspect::Fames);
-        Solver::add_composite_edges(&mut graph, Aspect::Ira, Aspect::Telum, Aspect::Ignis);
-        Solver::add_composite_edges(&mut graph, Aspect::Iter, Aspect::Motus, Aspect::Terra);
-        Solver::add_composite_edges(&mut graph, Aspect::Limus, Aspect::Victus, Aspect::Aqua);
-        Solver::add_composite_edges(&mut graph, Aspect::Lucrum, Aspect::Humanus, Aspect::Fames);
-        Solver::add_composite_edges(&mut graph, Aspect::Lux, Aspect::Aer, Aspect::Ignis);
-        Solver::add_composite_edges(&mut graph, Aspect::Luxuria, Aspect::Corpus, Aspect::Fames);
-        Solver::add_composite_edges(&mut graph, Aspect::Machina, Aspect::Motus, Aspect::Instrumentum);
-        Solver::add_composite_edges(&mut graph, Aspect::Magneto, Aspect::Metallum, Aspect::Iter);
-        Solver::add_composite_edges(&mut graph, Aspect::Messis, Aspect::Herba, Aspect::Humanus);
-        Solver::add_composite_edges(&mut graph, Aspect::Metallum, Aspect::Terra, Aspect::Vitreus);
-        Solver::add_composite_edges(&mut graph, Aspect::Meto, Aspect::Messis, Aspect::Instrumentum);
-        Solver::add_composite_edges(&mut graph, Aspect::Mortuus, Aspect::Victus, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Motus, Aspect::Aer, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Nebrisum, Aspect::Perfodio, Aspect::Lucrum);
-        Solver::add_composite_edges(&mut graph, Aspect::Pannus, Aspect::Instrumentum, Aspect::Bestia);
-        Solver::add_composite_edges(&mut graph, Aspect::Perfodio, Aspect::Humanus, Aspect::Terra);
-        Solver::add_composite_edges(&mut graph, Aspect::Permutatio, Aspect::Perditio, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Potentia, Aspect::Ordo, Aspect::Ignis);
-        Solver::add_composite_edges(&mut graph, Aspect::Praecantatio, Aspect::Vacuos, Aspect::Potentia);
-        Solver::add_composite_edges(&mut graph, Aspect::Radio, Aspect::Lux, Aspect::Potentia);
-        Solver::add_composite_edges(&mut graph, Aspect::Sano, Aspect::Victus, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Sensus, Aspect::Aer, Aspect::Spiritus);
-        Solver::add_composite_edges(&mut graph, Aspect::Spiritus, Aspect::Victus, Aspect::Mortuus);
-        Solver::add_composite_edges(&mut graph, Aspect::Strontio, Aspect::Cognitio, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Superbia, Aspect::Volatus, Aspect::Vacuos);
-        Solver::add_composite_edges(&mut graph, Aspect::Tabernus, Aspect::Tutamen, Aspect::Iter);
-        Solver::add_composite_edges(&mut graph, Aspect::Telum, Aspect::Instrumentum, Aspect::Ignis);
-        Solver::add_composite_edges(&mut graph, Aspect::Tempestas, Aspect::Aer, Aspect::Aqua);
-        Solver::add_composite_edges(&mut graph, Aspect::Tempus, Aspect::Vacuos, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Tenebrae, Aspect::Vacuos, Aspect::Lux);
-        Solver::add_composite_edges(&mut graph, Aspect::Tutamen, Aspect::Instrumentum, Aspect::Terra);
-        Solver::add_composite_edges(&mut graph, Aspect::Vacuos, Aspect::Aer, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Venenum, Aspect::Aqua, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Victus, Aspect::Aqua, Aspect::Terra);
-        Solver::add_composite_edges(&mut graph, Aspect::Vinculum, Aspect::Motus, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Vitium, Aspect::Praecantatio, Aspect::Perditio);
-        Solver::add_composite_edges(&mut graph, Aspect::Vitreus, Aspect::Terra, Aspect::Ordo);
-        Solver::add_composite_edges(&mut graph, Aspect::Volatus, Aspect::Aer, Aspect::Motus);
+        for composite in edition.aspects() {
+            if let Some((component_a, component_b)) = composite.components(edition) {
+                graph.add_indirectional_edge(composite, component_a);
+                graph.add_indirectional_edge(composite, component_b);
+            }
+        }
 
         graph
     }
@@ -153,13 +218,315 @@ impl Solver {
 
 pub type AspectPath = Vec<Aspect>;
 
-pub struct AspectPaths {
-    pub paths: Vec<Vec<Aspect>>,
-    pub price: u32,
+/// Minimal price to reach an aspect after a number of steps, every aspect it can be reached from at that price,
+/// and how many cheapest walks lead there.
+struct PathStep {
+    price: u32,
+    predecessors: Vec<Aspect>,
+    count: u64,
+}
+
+impl PathStep {
+    fn relax(layer: &mut HashMap<Aspect, PathStep>, aspect: Aspect, price: u32, predecessor: Aspect, count: u64) {
+        match layer.get_mut(&aspect) {
+            Some(step) if price > step.price => {}
+            Some(step) if price == step.price => {
+                step.predecessors.push(predecessor);
+                step.count = step.count.saturating_add(count);
+            }
+            _ => {
+                layer.insert(
+                    aspect,
+                    PathStep {
+                        price,
+                        predecessors: vec![predecessor],
+                        count,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// One of the cheapest partial paths reaching an aspect, pointing at the rank of the partial path it extends.
+struct RankedStep {
+    price: u32,
+    predecessor: Option<(Aspect, usize)>,
+}
+
+/// Result of [`Solver::find_paths`]: the cheapest walks from one aspect for every length as a DAG of predecessors,
+/// `layers[i]` holding the aspects that can be placed at index `i` of a path.
+pub struct PathTable<'a> {
+    aspect_graph: &'a Graph<Aspect>,
+    layers: Vec<HashMap<Aspect, PathStep>>,
 }
 
-impl AspectPaths {
-    pub fn new(paths: Vec<AspectPath>, price: u32) -> Self {
-        Self { paths, price }
+impl PathTable<'_> {
+    /// Price of the cheapest paths to `end` with the given number of aspects, `None` if there is none.
+    pub fn price(&self, end: Aspect, length: u8) -> Option<u32> {
+        self.end_step(end, length).map(|step| step.price)
+    }
+
+    /// Number of distinct cheapest paths to `end` with the given number of aspects.
+    pub fn count(&self, end: Aspect, length: u8) -> u64 {
+        self.end_step(end, length).map(|step| step.count).unwrap_or_default()
+    }
+
+    /// Lazily enumerates all cheapest paths to `end` with the given number of aspects, they all cost [`PathTable::price`].
+    pub fn paths(&self, end: Aspect, length: u8) -> AspectPaths<'_> {
+        let end_step = self.end_step(end, length);
+        let stack = end_step.as_ref().map(|_| vec![(end, 0)]).unwrap_or_default();
+        AspectPaths {
+            layers: &self.layers,
+            end_predecessors: end_step.map(|step| step.predecessors).unwrap_or_default(),
+            stack,
+            length: length as usize,
+        }
+    }
+
+    fn end_step(&self, end: Aspect, length: u8) -> Option<PathStep> {
+        let before_end = self.layers.get((length as usize).checked_sub(2)?)?;
+        let mut end_layer = HashMap::new();
+        for neighbour in self.aspect_graph.neighbours_cloned_iter(end) {
+            if let Some(step) = before_end.get(&neighbour) {
+                PathStep::relax(&mut end_layer, end, step.price, neighbour, step.count);
+            }
+        }
+
+        end_layer.remove(&end)
+    }
+}
+/// Iterator over the cheapest paths of one length, walking the predecessor DAG back from the end aspect.
+pub struct AspectPaths<'a> {
+    layers: &'a [HashMap<Aspect, PathStep>],
+    end_predecessors: Vec<Aspect>,
+    /// Aspects from the end towards the start, each with the index of the predecessor taken next
+    stack: Vec<(Aspect, usize)>,
+    length: usize,
+}
+
+impl AspectPaths<'_> {
+    fn predecessors_at(&self, depth: usize) -> &[Aspect] {
+        let (aspect, _) = self.stack[depth];
+        match self.length - 1 - depth {
+            0 => &[],
+            index if index == self.length - 1 => &self.end_predecessors,
+            index => &self.layers[index][&aspect].predecessors,
+        }
+    }
+
+    /// Moves to the next choice of the deepest aspect that has one left, dropping the aspects after it.
+    fn advance(&mut self) -> bool {
+        while let Some(depth) = self.stack.len().checked_sub(1) {
+            let choice = self.stack[depth].1 + 1;
+            if choice < self.predecessors_at(depth).len() {
+                self.stack[depth].1 = choice;
+                return true;
+            }
+            self.stack.pop();
+        }
+
+        false
+    }
+}
+
+impl Iterator for AspectPaths<'_> {
+    type Item = AspectPath;
+
+    fn next(&mut self) -> Option<AspectPath> {
+        // A complete path is still on the stack from the previous call
+        if self.stack.len() == self.length && !self.advance() {
+            return None;
+        }
+        if self.stack.is_empty() {
+            return None;
+        }
+
+        while self.stack.len() < self.length {
+            let depth = self.stack.len() - 1;
+            let predecessor = self.predecessors_at(depth)[self.stack[depth].1];
+            self.stack.push((predecessor, 0));
+        }
+
+        Some(self.stack.iter().rev().map(|&(aspect, _)| aspect).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const MAX_LENGTH: u8 = 6;
+
+    /// Small linear congruential generator, enough to vary the inventories without pulling in a crate.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) % bound
+        }
+    }
+
+    /// Random TC4 solver where about a fifth of the aspects are undiscovered, some are out of stock and
+    /// the low amounts make tied prices common. Odd seeds forbid steps below a reserve, even seeds penalize them.
+    fn random_solver(seed: u64) -> Solver {
+        let mut lcg = Lcg(seed);
+        let mut inventory = HashMap::new();
+        let mut thresholds = HashMap::new();
+        for aspect in Edition::Tc4.aspects() {
+            if lcg.below(5) > 0 {
+                inventory.insert(aspect, lcg.below(6) as u16);
+            }
+            if lcg.below(8) == 0 {
+                thresholds.insert(aspect, 3);
+            }
+        }
+        let policy = if seed % 2 == 1 { ReservePolicy::Forbid } else { ReservePolicy::Penalize };
+        let research_skill = [ResearchSkill::None, ResearchSkill::Expertise, ResearchSkill::Mastery][lcg.below(3) as usize];
+
+        Solver::new(AspectInventory::new(inventory, Edition::Tc4), Reserves::new(thresholds, policy), research_skill)
+    }
+
+    /// Every walk from `start` up to `MAX_LENGTH` aspects with its price, keyed by end aspect and length.
+    fn enumerate_walks(solver: &Solver, start: Aspect) -> HashMap<(Aspect, u8), Vec<(u32, AspectPath)>> {
+        fn extend(solver: &Solver, path: &mut AspectPath, price: u32, walks: &mut HashMap<(Aspect, u8), Vec<(u32, AspectPath)>>) {
+            let last = *path.last().unwrap();
+            for neighbour in solver.neighbours(last).collect::<Vec<_>>() {
+                path.push(neighbour);
+                walks.entry((neighbour, path.len() as u8)).or_default().push((price, path.clone()));
+                if path.len() < MAX_LENGTH as usize {
+                    if let Some(step_price) = solver.step_price(neighbour) {
+                        extend(solver, path, price + step_price, walks);
+                    }
+                }
+                path.pop();
+            }
+        }
+
+        let mut walks = HashMap::new();
+        extend(solver, &mut vec![start], 0, &mut walks);
+        walks
+    }
+
+    fn cheapest(walks: &[(u32, AspectPath)]) -> (u32, HashSet<AspectPath>) {
+        let price = walks.iter().map(|&(price, _)| price).min().unwrap();
+        (price, walks.iter().filter(|walk| walk.0 == price).map(|walk| walk.1.clone()).collect())
+    }
+
+    #[test]
+    fn find_paths_matches_brute_force() {
+        for seed in 0..4 {
+            let solver = random_solver(seed);
+            for start in Edition::Tc4.aspects() {
+                let path_table = solver.find_paths(start, MAX_LENGTH);
+                let walks = enumerate_walks(&solver, start);
+                for end in Edition::Tc4.aspects() {
+                    for length in 2..=MAX_LENGTH {
+                        let paths: Vec<AspectPath> = path_table.paths(end, length).collect();
+                        match walks.get(&(end, length)) {
+                            Some(walks) => {
+                                let (price, ties) = cheapest(walks);
+                                let context = format!("seed {} from {:?} to {:?} with {} aspects", seed, start, end, length);
+                                assert_eq!(path_table.price(end, length), Some(price), "{}", context);
+                                assert_eq!(path_table.count(end, length), ties.len() as u64, "{}", context);
+                                assert_eq!(paths.len(), ties.len(), "{}", context);
+                                assert_eq!(paths.into_iter().collect::<HashSet<_>>(), ties, "{}", context);
+                            }
+                            None => {
+                                assert_eq!(path_table.price(end, length), None);
+                                assert_eq!(path_table.count(end, length), 0);
+                                assert!(paths.is_empty());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn find_best_paths_matches_brute_force() {
+        let solver = random_solver(7);
+        for start in [Aspect::Aer, Aspect::Lux, Aspect::Humanus] {
+            let walks = enumerate_walks(&solver, start);
+            for end in Edition::Tc4.aspects() {
+                for length in 2..=MAX_LENGTH {
+                    let best_paths = solver.find_best_paths(start, end, length, 5);
+                    let mut prices: Vec<u32> = walks.get(&(end, length)).map(|walks| walks.iter().map(|&(price, _)| price).collect()).unwrap_or_default();
+                    prices.sort();
+                    prices.truncate(5);
+                    assert_eq!(best_paths.iter().map(|&(price, _)| price).collect::<Vec<_>>(), prices);
+                    for (price, path) in best_paths {
+                        assert!(walks[&(end, length)].contains(&(price, path)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_make_a_free_path_of_length_two() {
+        let solver = random_solver(0);
+        let path_table = solver.find_paths(Aspect::Lux, 2);
+        assert_eq!(path_table.price(Aspect::Aer, 2), Some(0));
+        assert_eq!(path_table.count(Aspect::Aer, 2), 1);
+        assert_eq!(path_table.paths(Aspect::Aer, 2).collect::<Vec<_>>(), vec![vec![Aspect::Lux, Aspect::Aer]]);
+        assert_eq!(path_table.price(Aspect::Aqua, 2), None);
+        assert_eq!(path_table.paths(Aspect::Aqua, 2).count(), 0);
+    }
+
+    #[test]
+    fn undiscovered_aspects_are_never_placed() {
+        let primals = Edition::Tc4.aspects().into_iter().filter(Aspect::is_primal).map(|aspect| (aspect, 10)).collect();
+        let solver = Solver::new(AspectInventory::new(primals, Edition::Tc4), Reserves::default(), ResearchSkill::None);
+        let path_table = solver.find_paths(Aspect::Aer, MAX_LENGTH);
+        for length in 3..=MAX_LENGTH {
+            assert_eq!(path_table.price(Aspect::Ignis, length), None);
+            assert_eq!(path_table.count(Aspect::Ignis, length), 0);
+            assert_eq!(path_table.paths(Aspect::Ignis, length).count(), 0);
+            assert!(solver.find_best_paths(Aspect::Aer, Aspect::Ignis, length, 3).is_empty());
+        }
+
+        for seed in 0..4 {
+            let solver = random_solver(seed);
+            let path_table = solver.find_paths(Aspect::Aer, MAX_LENGTH);
+            for end in Edition::Tc4.aspects() {
+                for length in 3..=MAX_LENGTH {
+                    for path in path_table.paths(end, length) {
+                        assert!(path[1..path.len() - 1].iter().all(|&aspect| solver.aspect_inventory().is_discovered(aspect)), "{:?}", path);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn research_skill_scales_prices_without_changing_paths() {
+        let inventory = Edition::Tc4
+            .aspects()
+            .into_iter()
+            .enumerate()
+            .map(|(index, aspect)| (aspect, (index * 7 % 23) as u16))
+            .collect();
+        let base = Solver::new(AspectInventory::new(inventory, Edition::Tc4), Reserves::default(), ResearchSkill::None);
+        for research_skill in [ResearchSkill::Expertise, ResearchSkill::Mastery] {
+            let skilled = Solver::new(base.aspect_inventory().clone(), base.reserves().clone(), research_skill);
+            let (base_table, skilled_table) = (base.find_paths(Aspect::Ordo, MAX_LENGTH), skilled.find_paths(Aspect::Ordo, MAX_LENGTH));
+            for end in Edition::Tc4.aspects() {
+                for length in 2..=MAX_LENGTH {
+                    let scaled = base_table
+                        .price(end, length)
+                        .map(|price| price / base.research_skill().consume_percent() * research_skill.consume_percent());
+                    assert_eq!(skilled_table.price(end, length), scaled);
+                    assert_eq!(
+                        skilled_table.paths(end, length).collect::<HashSet<_>>(),
+                        base_table.paths(end, length).collect::<HashSet<_>>()
+                    );
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,193 @@
+mod archive;
+mod cache;
+mod ftp;
+mod local;
+#[cfg(test)]
+mod memory;
+mod retry;
+mod saving;
+mod sftp;
+
+pub use archive::ArchiveSource;
+pub use cache::CachedSource;
+pub use ftp::FtpSource;
+pub use local::LocalSource;
+#[cfg(test)]
+pub use memory::MemorySource;
+pub use retry::RetryingSource;
+pub use saving::SavingSource;
+pub use sftp::SftpSource;
+
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Plain FTP, sends the password in cleartext
+    #[default]
+    Ftp,
+    /// FTP upgraded to TLS with `AUTH TLS`, verifying the server certificate
+    Ftps,
+    /// SSH file transfer
+    Sftp,
+}
+
+/// How FTP data connections are opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FtpMode {
+    /// The client connects to the server, works behind NAT and most firewalls
+    #[default]
+    Passive,
+    /// The server connects back to the client, for servers without passive mode
+    Active,
+}
+
+/// Why a source could not deliver a file, so callers can tell the user what to fix
+/// and only retry failures that may go away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceError {
+    /// The server rejected the username, password or key
+    Auth(String),
+    /// The player file or directory does not exist
+    NotFound(String),
+    /// The server could not be reached, timed out or dropped the connection
+    Network(String),
+    /// Invalid settings or unreadable data, retrying does not help
+    Other(String),
+}
+
+impl SourceError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SourceError::Network(_))
+    }
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Auth(message) => write!(f, "Authentication failed: {}. Check the configured credentials", message),
+            SourceError::NotFound(message) => write!(f, "Not found: {}. Check the username, world directory and path template", message),
+            SourceError::Network(message) => write!(f, "Network failure: {}. Check the server address and your connection", message),
+            SourceError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<SourceError> for String {
+    fn from(error: SourceError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Connection settings shared by the remote sources.
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    pub protocol: Protocol,
+    pub address: String,
+    pub username: String,
+    pub password: Option<String>,
+    /// Private key used instead of the password over SFTP, the password then unlocks the key
+    pub ssh_key: Option<PathBuf>,
+    /// Certificate trusted instead of the system roots over FTPS
+    pub tls_pinned_cert: Option<PathBuf>,
+    pub world_dir: Option<String>,
+    pub path_template: String,
+    pub ftp_mode: FtpMode,
+    /// Applies to connecting and to every read on the connection
+    pub timeout: Duration,
+    /// How many times network failures are retried, reconnecting each time
+    pub retries: u32,
+}
+
+/// Opens a source, called again whenever a wrapper has to reconnect.
+pub type Connect = Box<dyn FnMut() -> Result<Box<dyn InventorySource>, SourceError> + Send>;
+
+/// Opens the remote source selected by the configured protocol, retrying network failures.
+pub fn connect(config: &RemoteConfig) -> Result<Box<dyn InventorySource>, SourceError> {
+    Ok(Box::new(RetryingSource::connect(config.clone())?))
+}
+
+/// Splits `host`, `host:port`, `[ipv6]`, `[ipv6]:port` or a bare IPv6 address, defaulting to the given port.
+pub fn host_and_port(address: &str, default_port: u16) -> Result<(String, u16), SourceError> {
+    let parse_port = |port: &str| port.parse().map_err(|_| SourceError::Other(format!("'{}' is not a valid port in '{}'", port, address)));
+
+    if let Some(bracketed) = address.strip_prefix('[') {
+        let (host, rest) = bracketed
+            .split_once(']')
+            .ok_or_else(|| SourceError::Other(format!("'{}' is missing the closing ']'", address)))?;
+        let port = match rest {
+            "" => default_port,
+            _ => parse_port(rest.strip_prefix(':').unwrap_or(rest))?,
+        };
+        return Ok((host.to_string(), port));
+    }
+
+    match address.split_once(':') {
+        // More than one colon is an IPv6 address without port
+        Some((host, port)) if !port.contains(':') => Ok((host.to_string(), parse_port(port)?)),
+        _ => Ok((address.to_string(), default_port)),
+    }
+}
+
+/// Somewhere the raw `.thaum` player files can be read from.
+pub trait InventorySource: Send {
+    /// Returns the gzip compressed `.thaum` file of the player.
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError>;
+
+    /// Returns the sorted names of all players with a `.thaum` file, if the source can list them.
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        Err(SourceError::Other("This inventory source cannot list players".to_string()))
+    }
+
+    /// Returns the gzip compressed vanilla `<uuid>.dat` file of the player, holding their items.
+    fn read_player_dat(&mut self, _uuid: &str) -> Result<Vec<u8>, SourceError> {
+        Err(SourceError::Other("This inventory source cannot read player .dat files".to_string()))
+    }
+
+    /// Returns the server's `usercache.json`, mapping usernames to UUIDs.
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        Err(SourceError::Other("This inventory source cannot read usercache.json, give the UUID instead".to_string()))
+    }
+
+    /// Returns when the `.thaum` file of the player last changed, or `None` if the source cannot tell
+    /// and the file has to be read again to find out.
+    fn modified(&mut self, _username: &str) -> Result<Option<SystemTime>, SourceError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_and_port_uses_the_default_port() {
+        assert_eq!(host_and_port("mc.example.org", 22), Ok(("mc.example.org".to_string(), 22)));
+        assert_eq!(host_and_port("mc.example.org:2222", 22), Ok(("mc.example.org".to_string(), 2222)));
+        assert_eq!(host_and_port("10.0.0.1:2021", 21), Ok(("10.0.0.1".to_string(), 2021)));
+        assert_eq!(host_and_port("10.0.0.1", 21), Ok(("10.0.0.1".to_string(), 21)));
+    }
+
+    #[test]
+    fn host_and_port_accepts_ipv6() {
+        assert_eq!(host_and_port("::1", 21), Ok(("::1".to_string(), 21)));
+        assert_eq!(host_and_port("fe80::1:2", 22), Ok(("fe80::1:2".to_string(), 22)));
+        assert_eq!(host_and_port("[::1]", 25575), Ok(("::1".to_string(), 25575)));
+        assert_eq!(host_and_port("[::1]:2222", 22), Ok(("::1".to_string(), 2222)));
+    }
+
+    #[test]
+    fn host_and_port_rejects_invalid_ports() {
+        assert!(matches!(host_and_port("mc.example.org:ssh", 22), Err(SourceError::Other(_))));
+        assert!(matches!(host_and_port("[::1]:99999", 22), Err(SourceError::Other(_))));
+        assert!(matches!(host_and_port("[::1", 22), Err(SourceError::Other(_))));
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::PathBuf,
+};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use super::{InventorySource, SourceError};
+
+const THAUM_EXTENSION: &str = ".thaum";
+const DAT_EXTENSION: &str = ".dat";
+const PLAYER_DATA_DIR: &str = "playerdata";
+const USER_CACHE_FILE_NAME: &str = "usercache.json";
+
+/// Reads the player files straight out of a `.zip`, `.tar` or `.tar.gz` world backup without extracting it.
+/// The archive is only scanned once when opened, however many players are read afterwards.
+pub struct ArchiveSource {
+    path: PathBuf,
+    entries: ArchiveEntries,
+}
+
+enum ArchiveEntries {
+    /// Zip archives have a central directory, so entries are indexed by path and only decompressed when read
+    Zip {
+        archive: ZipArchive<BufReader<File>>,
+        indices: Vec<(String, usize)>,
+    },
+    /// Tar archives can only be read front to back, so the player files are kept from a single pass
+    Tar { files: Vec<(String, Vec<u8>)> },
+}
+
+impl ArchiveSource {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_lowercase();
+        let file = File::open(&path)
+            .map(BufReader::new)
+            .map_err(|error| format!("Could not open '{}': {}", path.display(), error))?;
+        let entries = if file_name.ends_with(".zip") {
+            ArchiveSource::index_zip(file)?
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            ArchiveSource::read_tar(GzDecoder::new(file))?
+        } else if file_name.ends_with(".tar") {
+            ArchiveSource::read_tar(file)?
+        } else {
+            return Err(format!("'{}' is not a .zip, .tar or .tar.gz archive", path.display()));
+        };
+
+        Ok(ArchiveSource { path, entries })
+    }
+
+    fn index_zip(file: BufReader<File>) -> Result<ArchiveEntries, String> {
+        let archive = ZipArchive::new(file).map_err(|error| format!("Could not read zip archive: {}", error))?;
+        let mut indices = Vec::with_capacity(archive.len());
+        for index in 0..archive.len() {
+            if let Some(name) = archive.name_for_index(index) {
+                let name = name.map_err(|error| format!("Could not read zip entry name: {}", error))?;
+                indices.push((name.replace('\\', "/"), index));
+            }
+        }
+
+        Ok(ArchiveEntries::Zip { archive, indices })
+    }
+
+    fn read_tar(reader: impl Read) -> Result<ArchiveEntries, String> {
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive.entries().map_err(|error| format!("Could not read tar archive: {}", error))?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let mut entry = entry.map_err(|error| format!("Could not read tar entry: {}", error))?;
+            let entry_path = entry
+                .path()
+                .map_err(|error| format!("Could not read tar entry path: {}", error))?
+                .to_string_lossy()
+                .into_owned();
+            if !ArchiveSource::is_player_file(&entry_path) {
+                continue;
+            }
+
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .map_err(|error| format!("Could not read '{}' from archive: {}", entry_path, error))?;
+            files.push((entry_path, content));
+        }
+
+        Ok(ArchiveEntries::Tar { files })
+    }
+
+    fn is_player_file(entry_path: &str) -> bool {
+        ArchiveSource::player_of_entry(entry_path, THAUM_EXTENSION).is_some()
+            || ArchiveSource::player_of_entry(entry_path, DAT_EXTENSION).is_some()
+            || ArchiveSource::is_user_cache(entry_path)
+    }
+
+    fn is_user_cache(entry_path: &str) -> bool {
+        entry_path.rsplit('/').next() == Some(USER_CACHE_FILE_NAME)
+    }
+
+    /// Returns the player name or UUID if the archive entry is a file with the given extension inside a `playerdata` directory.
+    fn player_of_entry<'a>(entry_path: &'a str, extension: &str) -> Option<&'a str> {
+        let (dir, file_name) = entry_path.trim_end_matches('/').rsplit_once('/')?;
+        if dir.rsplit('/').next()? != PLAYER_DATA_DIR {
+            return None;
+        }
+
+        file_name.strip_suffix(extension).filter(|player| !player.is_empty())
+    }
+
+    fn entry_paths(&self) -> Vec<&str> {
+        match &self.entries {
+            ArchiveEntries::Zip { indices, .. } => indices.iter().map(|(entry_path, _)| entry_path.as_str()).collect(),
+            ArchiveEntries::Tar { files } => files.iter().map(|(entry_path, _)| entry_path.as_str()).collect(),
+        }
+    }
+
+    /// Reads the first entry accepted by `is_wanted`, described by `description` when it is missing.
+    fn read_entry(&mut self, is_wanted: impl Fn(&str) -> bool, description: &str) -> Result<Vec<u8>, SourceError> {
+        let missing = || SourceError::NotFound(format!("Archive '{}' does not contain {}", self.path.display(), description));
+        match &mut self.entries {
+            ArchiveEntries::Zip { archive, indices } => {
+                let (entry_path, index) = indices.iter().find(|(entry_path, _)| is_wanted(entry_path)).ok_or_else(missing)?;
+                let mut entry = archive
+                    .by_index(*index)
+                    .map_err(|error| SourceError::Other(format!("Could not read zip entry '{}': {}", entry_path, error)))?;
+                let mut content = Vec::new();
+                entry
+                    .read_to_end(&mut content)
+                    .map_err(|error| SourceError::Other(format!("Could not read '{}' from archive: {}", entry_path, error)))?;
+                Ok(content)
+            }
+            ArchiveEntries::Tar { files } => files
+                .iter()
+                .find(|(entry_path, _)| is_wanted(entry_path))
+                .map(|(_, content)| content.clone())
+                .ok_or_else(missing),
+        }
+    }
+}
+
+impl InventorySource for ArchiveSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        self.read_entry(
+            |entry_path| ArchiveSource::player_of_entry(entry_path, THAUM_EXTENSION) == Some(username),
+            &format!("{}/{}{}", PLAYER_DATA_DIR, username, THAUM_EXTENSION),
+        )
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        self.read_entry(
+            |entry_path| ArchiveSource::player_of_entry(entry_path, DAT_EXTENSION) == Some(uuid),
+            &format!("{}/{}{}", PLAYER_DATA_DIR, uuid, DAT_EXTENSION),
+        )
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.read_entry(ArchiveSource::is_user_cache, USER_CACHE_FILE_NAME)
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        let mut players: Vec<String> = self
+            .entry_paths()
+            .into_iter()
+            .filter_map(|entry_path| ArchiveSource::player_of_entry(entry_path, THAUM_EXTENSION))
+            .map(str::to_owned)
+            .collect();
+        players.sort();
+        players.dedup();
+
+        Ok(players)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Cursor, Write},
+        process,
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    const UUID: &str = "8c6e1a2b-1111-2222-3333-444455556666";
+
+    /// A backup with the player files inside a top directory, after a larger unrelated file.
+    fn backup_entries() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("backup/World/region/r.0.0.mca", vec![7; 64 * 1024]),
+            ("backup/World/playerdata/alice.thaum", b"alice thaum".to_vec()),
+            ("backup/World/playerdata/bob.thaum", b"bob thaum".to_vec()),
+            ("backup/World/playerdata/8c6e1a2b-1111-2222-3333-444455556666.dat", b"alice dat".to_vec()),
+            ("backup/World/stats/alice.thaum", b"not player data".to_vec()),
+            ("backup/usercache.json", b"[]".to_vec()),
+        ]
+    }
+
+    fn zip_archive(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in entries {
+            writer.start_file(*path, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn tar_archive(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_slice()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn open(file_name: &str, content: &[u8]) -> ArchiveSource {
+        let dir = std::env::temp_dir().join(format!("{}-{}-archive", env!("CARGO_PKG_NAME"), process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        fs::write(&path, content).unwrap();
+        let source = ArchiveSource::open(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+        source
+    }
+
+    fn assert_reads_backup(mut source: ArchiveSource) {
+        assert_eq!(source.read_thaum("alice").unwrap(), b"alice thaum");
+        assert_eq!(source.read_thaum("bob").unwrap(), b"bob thaum");
+        assert_eq!(source.read_player_dat(UUID).unwrap(), b"alice dat");
+        assert_eq!(source.read_user_cache().unwrap(), b"[]");
+        assert_eq!(source.list_players().unwrap(), vec!["alice".to_string(), "bob".to_string()]);
+        // Read twice to make sure reading does not use up an entry
+        assert_eq!(source.read_thaum("alice").unwrap(), b"alice thaum");
+
+        match source.read_thaum("carol") {
+            Err(SourceError::NotFound(message)) => assert!(message.ends_with("does not contain playerdata/carol.thaum"), "{}", message),
+            other => panic!("expected a missing file, got {:?}", other),
+        }
+        assert!(matches!(source.read_player_dat("alice"), Err(SourceError::NotFound(_))));
+    }
+
+    #[test]
+    fn reads_zip_backups() {
+        assert_reads_backup(open("backup.zip", &zip_archive(&backup_entries())));
+    }
+
+    #[test]
+    fn reads_tar_backups() {
+        assert_reads_backup(open("backup.tar", &tar_archive(&backup_entries())));
+        assert_reads_backup(open("backup.tar.gz", &gzip(&tar_archive(&backup_entries()))));
+    }
+
+    #[test]
+    fn tar_backups_keep_only_player_files() {
+        let ArchiveEntries::Tar { files } = open("backup.tgz", &gzip(&tar_archive(&backup_entries()))).entries else {
+            panic!("expected a tar archive");
+        };
+        let paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "backup/World/playerdata/alice.thaum",
+                "backup/World/playerdata/bob.thaum",
+                "backup/World/playerdata/8c6e1a2b-1111-2222-3333-444455556666.dat",
+                "backup/usercache.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(ArchiveSource::open(PathBuf::from("backup.7z")).is_err());
+        let path = std::env::temp_dir().join(format!("{}-{}-archive-broken.zip", env!("CARGO_PKG_NAME"), process::id()));
+        fs::write(&path, b"not a zip").unwrap();
+        assert!(ArchiveSource::open(path.clone()).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
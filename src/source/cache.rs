@@ -0,0 +1,415 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use super::{Connect, InventorySource, RemoteConfig, SourceError};
+
+const THAUM_EXTENSION: &str = ".thaum";
+const DAT_EXTENSION: &str = ".dat";
+const USER_CACHE_FILE: &str = "usercache.json";
+
+/// Keeps the last downloaded `.thaum` or `.dat` file of every player, and `usercache.json`, per server and
+/// world. Fresh copies are used without connecting at all, and stale copies are used with a warning when
+/// the network fails.
+pub struct CachedSource {
+    dir: PathBuf,
+    ttl: Duration,
+    connect: Connect,
+    /// Only successful connections are kept, a failed attempt is repeated on the next call
+    inner: Option<Box<dyn InventorySource>>,
+    warned: bool,
+}
+
+impl CachedSource {
+    /// `<cache dir>/thaumcraft-research-solver`, e.g. `~/.cache` on Linux.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+    }
+
+    /// Wraps a remote source, which is only connected once a cached file is missing or older than the TTL.
+    pub fn new(cache_dir: PathBuf, config: &RemoteConfig, ttl: Duration, connect: Connect) -> Self {
+        CachedSource {
+            dir: cache_dir.join(CachedSource::server_key(config)),
+            ttl,
+            connect,
+            inner: None,
+            warned: false,
+        }
+    }
+
+    /// `<protocol>-<username>@<address>+<world>+<path template>`, every part escaped so that different
+    /// servers, worlds or layouts never share a directory.
+    fn server_key(config: &RemoteConfig) -> String {
+        format!(
+            "{:?}-{}@{}+{}+{}",
+            config.protocol,
+            CachedSource::escape(&config.username),
+            CachedSource::escape(&config.address),
+            CachedSource::escape(config.world_dir.as_deref().unwrap_or_default()),
+            CachedSource::escape(&config.path_template)
+        )
+    }
+
+    /// Escapes everything but ASCII letters, digits, `.`, `-` and `_` as `%xx`, so that any text becomes a
+    /// file name of its own, which cannot point outside the cache directory.
+    fn escape(text: &str) -> String {
+        text.bytes()
+            .map(|byte| match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (byte as char).to_string(),
+                _ => format!("%{:02x}", byte),
+            })
+            .collect()
+    }
+
+    fn unescape(name: &str) -> Option<String> {
+        let mut bytes = Vec::with_capacity(name.len());
+        let mut rest = name.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            if byte == b'%' {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            } else {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+
+        String::from_utf8(bytes).ok()
+    }
+
+    fn cache_path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(file_name)
+    }
+
+    fn inner(&mut self) -> Result<&mut Box<dyn InventorySource>, SourceError> {
+        if self.inner.is_none() {
+            self.inner = Some((self.connect)()?);
+        }
+
+        Ok(self.inner.as_mut().unwrap())
+    }
+
+    fn read_cached(&self, file_name: &str) -> Option<(Vec<u8>, Duration)> {
+        let path = self.cache_path(file_name);
+        let age = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?.elapsed().unwrap_or_default();
+        let content = fs::read(&path).ok()?;
+
+        Some((content, age))
+    }
+
+    fn write_cached(&self, file_name: &str, what: &str, content: &[u8]) {
+        let result = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.cache_path(file_name), content));
+        if let Err(error) = result {
+            println!("Warning: could not cache {}: {}", what, error);
+        }
+    }
+
+    /// Returns the cached file while it is fresh, and otherwise reads it from the server and caches it,
+    /// falling back to the stale copy when the network fails.
+    fn read_through(&mut self, file_name: &str, what: &str, read: impl FnOnce(&mut dyn InventorySource) -> Result<Vec<u8>, SourceError>) -> Result<Vec<u8>, SourceError> {
+        let cached = self.read_cached(file_name);
+        if let Some((content, age)) = &cached {
+            if *age < self.ttl {
+                return Ok(content.clone());
+            }
+        }
+
+        match self.inner().and_then(|inner| read(inner.as_mut())) {
+            Ok(content) => {
+                self.write_cached(file_name, what, &content);
+                Ok(content)
+            }
+            Err(error) => match cached {
+                Some((content, age)) if error.is_retryable() => {
+                    self.warn_once(&error);
+                    println!("Warning: using the cached copy of {}, stale since {} ago", what, CachedSource::format_age(age));
+                    Ok(content)
+                }
+                _ => Err(error),
+            },
+        }
+    }
+
+    fn warn_once(&mut self, error: &SourceError) {
+        if !self.warned {
+            println!("Warning: {}", error);
+            self.warned = true;
+        }
+    }
+
+    fn format_age(age: Duration) -> String {
+        let minutes = age.as_secs() / 60;
+        match (minutes / 60 / 24, minutes / 60 % 24, minutes % 60) {
+            (0, 0, minutes) => format!("{}m", minutes),
+            (0, hours, minutes) => format!("{}h {}m", hours, minutes),
+            (days, hours, _) => format!("{}d {}h", days, hours),
+        }
+    }
+}
+
+impl InventorySource for CachedSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        let file_name = format!("{}{}", CachedSource::escape(username), THAUM_EXTENSION);
+        self.read_through(&file_name, &format!("the inventory of {}", username), |inner| inner.read_thaum(username))
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        let file_name = format!("{}{}", CachedSource::escape(uuid), DAT_EXTENSION);
+        self.read_through(&file_name, &format!("the player data of {}", uuid), |inner| inner.read_player_dat(uuid))
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.read_through(USER_CACHE_FILE, USER_CACHE_FILE, |inner| inner.read_user_cache())
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        let error = match self.inner().and_then(|inner| inner.list_players()) {
+            Ok(players) => return Ok(players),
+            Err(error) if error.is_retryable() => error,
+            Err(error) => return Err(error),
+        };
+
+        let entries = fs::read_dir(&self.dir).map_err(|_| error.clone())?;
+        let mut players: Vec<String> = entries
+            .filter_map(|entry| CachedSource::unescape(entry.ok()?.file_name().to_str()?.strip_suffix(THAUM_EXTENSION)?))
+            .collect();
+        players.sort();
+
+        self.warn_once(&error);
+        println!("Warning: using the players found in the cache");
+        Ok(players)
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        self.inner()?.modified(username)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        process,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::source::{FtpMode, MemorySource, Protocol};
+
+    const STALE: Duration = Duration::ZERO;
+    const FRESH: Duration = Duration::from_secs(3600);
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}-cache-{}", env!("CARGO_PKG_NAME"), process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn test_config() -> RemoteConfig {
+        RemoteConfig {
+            protocol: Protocol::Ftp,
+            address: "127.0.0.1".to_string(),
+            username: "test".to_string(),
+            password: None,
+            ssh_key: None,
+            tls_pinned_cert: None,
+            world_dir: None,
+            path_template: String::new(),
+            ftp_mode: FtpMode::Passive,
+            timeout: Duration::from_secs(1),
+            retries: 0,
+        }
+    }
+
+    /// A cache over the memory source, counting how often it connects.
+    fn cached_source(dir: &Path, ttl: Duration, server: &MemorySource) -> (CachedSource, Arc<AtomicUsize>) {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let (server, counter) = (server.clone(), Arc::clone(&connections));
+        let connect: Connect = Box::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(server.clone()) as Box<dyn InventorySource>)
+        });
+
+        (CachedSource::new(dir.to_path_buf(), &test_config(), ttl, connect), connections)
+    }
+
+    #[test]
+    fn fresh_copies_are_used_without_connecting() {
+        let dir = test_dir("fresh");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+
+        let (mut source, _) = cached_source(&dir, FRESH, &server);
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+
+        server.set_thaum("alice", b"second".to_vec());
+        let (mut source, connections) = cached_source(&dir, FRESH, &server);
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_copies_are_refreshed() {
+        let dir = test_dir("refresh");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+
+        server.set_thaum("alice", b"second".to_vec());
+        assert_eq!(source.read_thaum("alice"), Ok(b"second".to_vec()));
+        assert_eq!(fs::read(dir.join(CachedSource::server_key(&test_config())).join("alice.thaum")).unwrap(), b"second");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_copies_are_used_when_the_network_fails() {
+        let dir = test_dir("network");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        source.read_thaum("alice").unwrap();
+
+        server.set_thaum("alice", b"second".to_vec());
+        server.fail_next(SourceError::Network("timed out".to_string()));
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_copies_are_used_when_the_server_is_unreachable() {
+        let dir = test_dir("unreachable");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+        cached_source(&dir, STALE, &server).0.read_thaum("alice").unwrap();
+
+        let connect: Connect = Box::new(|| Err(SourceError::Network("connection refused".to_string())));
+        let mut source = CachedSource::new(dir.clone(), &test_config(), STALE, connect);
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+        assert!(matches!(source.read_thaum("bob"), Err(SourceError::Network(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn server_is_reconnected_once_it_is_back() {
+        let dir = test_dir("reconnect");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+        cached_source(&dir, STALE, &server).0.read_thaum("alice").unwrap();
+
+        let server_is_up = Arc::new(AtomicUsize::new(0));
+        let (up, connected_server) = (Arc::clone(&server_is_up), server.clone());
+        let connect: Connect = Box::new(move || match up.load(Ordering::SeqCst) {
+            0 => Err(SourceError::Network("connection refused".to_string())),
+            _ => Ok(Box::new(connected_server.clone()) as Box<dyn InventorySource>),
+        });
+        let mut source = CachedSource::new(dir.clone(), &test_config(), STALE, connect);
+        assert_eq!(source.read_thaum("alice"), Ok(b"first".to_vec()));
+        assert!(matches!(source.modified("alice"), Err(SourceError::Network(_))));
+
+        server_is_up.store(1, Ordering::SeqCst);
+        server.set_thaum("alice", b"second".to_vec());
+        assert_eq!(source.modified("alice"), Ok(None));
+        assert_eq!(source.read_thaum("alice"), Ok(b"second".to_vec()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_failures_are_not_hidden_by_the_cache() {
+        let dir = test_dir("other");
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"first".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        source.read_thaum("alice").unwrap();
+
+        server.fail_next(SourceError::NotFound("deleted".to_string()));
+        assert!(matches!(source.read_thaum("alice"), Err(SourceError::NotFound(_))));
+        server.fail_next(SourceError::Auth("password changed".to_string()));
+        assert!(matches!(source.read_thaum("alice"), Err(SourceError::Auth(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn players_are_listed_from_the_cache_when_the_network_fails() {
+        let dir = test_dir("list");
+        let server = MemorySource::default();
+        server.set_thaum("bob", b"bob".to_vec());
+        server.set_thaum("alice", b"alice".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        assert_eq!(source.list_players(), Ok(vec!["alice".to_string(), "bob".to_string()]));
+        source.read_thaum("alice").unwrap();
+        source.read_thaum("bob").unwrap();
+
+        server.fail_next(SourceError::Network("timed out".to_string()));
+        assert_eq!(source.list_players(), Ok(vec!["alice".to_string(), "bob".to_string()]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn worlds_and_layouts_have_their_own_cache() {
+        let other_world = RemoteConfig {
+            world_dir: Some("Creative".to_string()),
+            ..test_config()
+        };
+        let other_layout = RemoteConfig {
+            path_template: "/{world}/players/{username}.thaum".to_string(),
+            ..test_config()
+        };
+        let keys = [test_config(), other_world, other_layout].map(|config| CachedSource::server_key(&config));
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[0], keys[2]);
+        assert_ne!(keys[1], keys[2]);
+        assert!(keys.iter().all(|key| !key.contains('/')), "{:?}", keys);
+    }
+
+    #[test]
+    fn usernames_cannot_leave_the_cache_dir() {
+        let dir = test_dir("escape");
+        let server = MemorySource::default();
+        server.set_thaum("../alice", b"outside".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        assert_eq!(source.read_thaum("../alice"), Ok(b"outside".to_vec()));
+
+        let server_dir = dir.join(CachedSource::server_key(&test_config()));
+        assert!(server_dir.join("..%2falice.thaum").is_file());
+        assert!(!dir.join("alice.thaum").exists());
+
+        server.fail_next(SourceError::Network("timed out".to_string()));
+        assert_eq!(source.list_players(), Ok(vec!["../alice".to_string()]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tc6_player_data_is_cached_for_offline_use() {
+        let dir = test_dir("tc6");
+        let server = MemorySource::default();
+        server.set_user_cache(r#"[{"name": "Carol", "uuid": "0000-carol"}]"#);
+        server.set_player_dat("0000-carol", b"carol".to_vec());
+        let (mut source, _) = cached_source(&dir, STALE, &server);
+        let user_cache = source.read_user_cache().unwrap();
+        assert_eq!(source.read_player_dat("0000-carol"), Ok(b"carol".to_vec()));
+
+        let connect: Connect = Box::new(|| Err(SourceError::Network("connection refused".to_string())));
+        let mut source = CachedSource::new(dir.clone(), &test_config(), STALE, connect);
+        assert_eq!(source.read_user_cache(), Ok(user_cache));
+        assert_eq!(source.read_player_dat("0000-carol"), Ok(b"carol".to_vec()));
+        assert!(matches!(source.read_player_dat("0000-dave"), Err(SourceError::Network(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
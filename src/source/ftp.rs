@@ -0,0 +1,227 @@
+use std::{
+    fs, io,
+    net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use suppaftp::{
+    native_tls::{Certificate, TlsConnector},
+    FtpError, FtpResult, NativeTlsConnector, NativeTlsFtpStream, Status,
+};
+
+use super::{FtpMode, InventorySource, Protocol, RemoteConfig, SourceError};
+use crate::layout::PlayerDataLayout;
+
+const DEFAULT_FTP_PORT: u16 = 21;
+
+/// suppaftp does not export its native-tls stream wrapper, only the connector producing it.
+type NativeTlsStream = <NativeTlsConnector as suppaftp::TlsConnector>::Stream;
+
+/// Downloads the player files over FTP, optionally upgraded to TLS with `AUTH TLS`.
+pub struct FtpSource {
+    ftp_stream: NativeTlsFtpStream,
+    layout: PlayerDataLayout,
+}
+
+impl FtpSource {
+    /// Connects and logs in. Over FTPS, the connection is secured before sending the password,
+    /// accepting only the pinned certificate when one is given and verifying against the system roots otherwise.
+    pub fn connect(config: &RemoteConfig) -> Result<Self, SourceError> {
+        let address = &config.address;
+        let (host, port) = super::host_and_port(address, DEFAULT_FTP_PORT)?;
+        let socket_address = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|error| SourceError::Network(format!("Could not resolve '{}': {}", address, error)))?
+            .next()
+            .ok_or_else(|| SourceError::Network(format!("Could not resolve '{}'", address)))?;
+
+        let timeout = config.timeout;
+        let control_stream = TcpStream::connect_timeout(&socket_address, timeout)
+            .and_then(|stream| stream.set_read_timeout(Some(timeout)).map(|_| stream))
+            .and_then(|stream| stream.set_write_timeout(Some(timeout)).map(|_| stream))
+            .map_err(|error| SourceError::Network(format!("Could not connect to FTP: {}", error)))?;
+        let mut ftp_stream = NativeTlsFtpStream::connect_with_stream(control_stream).map_err(|error| FtpSource::classify("Could not connect to FTP", error))?;
+
+        if config.protocol == Protocol::Ftps {
+            ftp_stream = match config.tls_pinned_cert.as_deref() {
+                Some(pinned_cert) => ftp_stream.into_secure(PinnedTlsConnector::new(pinned_cert)?, &host),
+                None => ftp_stream.into_secure(FtpSource::build_tls_connector()?, &host),
+            }
+            .map_err(|error| FtpSource::classify("Could not secure FTP connection with TLS", error))?;
+        }
+        ftp_stream = match config.ftp_mode {
+            FtpMode::Passive => ftp_stream.passive_stream_builder(move |address| {
+                let stream = TcpStream::connect_timeout(&address, timeout).map_err(FtpError::ConnectionError)?;
+                stream.set_read_timeout(Some(timeout)).map_err(FtpError::ConnectionError)?;
+                Ok(stream)
+            }),
+            FtpMode::Active => ftp_stream.active_mode(timeout),
+        };
+
+        let password = config.password.as_deref().ok_or_else(|| SourceError::Other("FTP password is not set".to_string()))?;
+        ftp_stream
+            .login(config.username.as_str(), password)
+            .map_err(|error| FtpSource::classify("Could not login to FTP", error))?;
+
+        let layout = PlayerDataLayout::resolve(config.world_dir.as_deref(), &config.path_template, |path| {
+            ftp_stream.retr_as_buffer(path).ok().map(|content| content.into_inner())
+        })
+        .map_err(SourceError::Other)?;
+
+        Ok(FtpSource { ftp_stream, layout })
+    }
+
+    fn download_file(&mut self, path: &str) -> Result<Vec<u8>, SourceError> {
+        self.ftp_stream
+            .retr_as_buffer(path)
+            .map(|content| content.into_inner())
+            .map_err(|error| FtpSource::classify(&format!("Could not retrieve '{}' from FTP", path), error))
+    }
+
+    fn build_tls_connector() -> Result<NativeTlsConnector, SourceError> {
+        let connector = TlsConnector::new().map_err(|error| SourceError::Other(format!("Could not create TLS connector: {}", error)))?;
+        Ok(NativeTlsConnector::from(connector))
+    }
+
+    /// Sorts FTP failures by their reply code: 530 is a rejected login, 550 a missing file,
+    /// and broken connections or 4xx replies such as 421 "too many connections" are worth retrying.
+    fn classify(context: &str, error: FtpError) -> SourceError {
+        let message = format!("{}: {}", context, error);
+        match &error {
+            FtpError::ConnectionError(io_error) if matches!(io_error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                SourceError::Network(format!("{}: no response within the timeout", context))
+            }
+            FtpError::ConnectionError(_) => SourceError::Network(message),
+            FtpError::UnexpectedResponse(response) => match response.status {
+                Status::NotLoggedIn => SourceError::Auth(message),
+                Status::FileUnavailable => SourceError::NotFound(message),
+                status if (400..500).contains(&status.code()) => SourceError::Network(message),
+                _ => SourceError::Other(message),
+            },
+            _ => SourceError::Other(message),
+        }
+    }
+}
+
+/// Accepts exactly the pinned certificate, typically the self-signed one of a server panel, whatever its
+/// issuer and host name. Chain and host name checks are skipped, the certificate itself is compared instead,
+/// for the control connection and every data connection alike.
+#[derive(Debug)]
+struct PinnedTlsConnector {
+    connector: TlsConnector,
+    pinned_der: Vec<u8>,
+}
+
+impl PinnedTlsConnector {
+    fn new(pinned_cert: &Path) -> Result<Self, SourceError> {
+        let pem = fs::read(pinned_cert).map_err(|error| SourceError::Other(format!("Could not read pinned certificate: {}", error)))?;
+        let pinned_der = Certificate::from_pem(&pem)
+            .and_then(|certificate| certificate.to_der())
+            .map_err(|error| SourceError::Other(format!("Pinned certificate is not a valid PEM certificate: {}", error)))?;
+        let connector = TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .build()
+            .map_err(|error| SourceError::Other(format!("Could not create TLS connector: {}", error)))?;
+
+        Ok(PinnedTlsConnector { connector, pinned_der })
+    }
+}
+
+impl suppaftp::TlsConnector for PinnedTlsConnector {
+    type Stream = NativeTlsStream;
+
+    fn connect(&self, domain: &str, stream: TcpStream) -> FtpResult<NativeTlsStream> {
+        let tls_stream = self.connector.connect(domain, stream).map_err(|error| FtpError::SecureError(error.to_string()))?;
+        let peer_der = tls_stream
+            .peer_certificate()
+            .and_then(|certificate| certificate.map(|certificate| certificate.to_der()).transpose())
+            .map_err(|error| FtpError::SecureError(format!("could not read the server certificate: {}", error)))?;
+        if peer_der.as_deref() != Some(self.pinned_der.as_slice()) {
+            return Err(FtpError::SecureError("the server certificate does not match the pinned certificate".to_string()));
+        }
+
+        Ok(NativeTlsStream::from(tls_stream))
+    }
+}
+
+impl InventorySource for FtpSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        let path = self.layout.thaum_path(username);
+        self.download_file(&path)
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        let path = self.layout.player_dat_path(uuid);
+        self.download_file(&path)
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.download_file(PlayerDataLayout::USER_CACHE_PATH)
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        let dir = self.layout.player_data_dir();
+        let file_names = self
+            .ftp_stream
+            .nlst(Some(dir))
+            .map_err(|error| FtpSource::classify(&format!("Could not list '{}' on FTP", dir), error))?;
+        let mut players: Vec<String> = file_names
+            .iter()
+            .filter_map(|file_name| self.layout.username_from_file_name(file_name))
+            .map(str::to_owned)
+            .collect();
+        players.sort();
+
+        Ok(players)
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        let path = self.layout.thaum_path(username);
+        let modified = self
+            .ftp_stream
+            .mdtm(&path)
+            .map_err(|error| FtpSource::classify(&format!("Could not get modification time of '{}' from FTP", path), error))?;
+
+        Ok(u64::try_from(modified.and_utc().timestamp()).ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use suppaftp::types::Response;
+
+    use super::*;
+
+    fn reply(status: Status) -> SourceError {
+        FtpSource::classify("context", FtpError::UnexpectedResponse(Response::new(status, b"reply".to_vec())))
+    }
+
+    #[test]
+    fn classify_sorts_replies_by_code() {
+        assert!(matches!(reply(Status::NotLoggedIn), SourceError::Auth(_)));
+        assert!(matches!(reply(Status::FileUnavailable), SourceError::NotFound(_)));
+        assert!(matches!(reply(Status::NotAvailable), SourceError::Network(_)));
+        assert!(matches!(reply(Status::CannotOpenDataConnection), SourceError::Network(_)));
+        assert!(matches!(reply(Status::RequestFileActionIgnored), SourceError::Network(_)));
+        assert!(matches!(reply(Status::BadCommand), SourceError::Other(_)));
+        assert!(matches!(reply(Status::ExceededStorage), SourceError::Other(_)));
+    }
+
+    #[test]
+    fn classify_retries_broken_connections() {
+        let connection_error = |kind| FtpSource::classify("context", FtpError::ConnectionError(io::Error::from(kind)));
+
+        match connection_error(io::ErrorKind::WouldBlock) {
+            SourceError::Network(message) => assert_eq!(message, "context: no response within the timeout"),
+            other => panic!("expected a network error, got {:?}", other),
+        }
+        assert!(matches!(connection_error(io::ErrorKind::TimedOut), SourceError::Network(_)));
+        assert!(matches!(connection_error(io::ErrorKind::ConnectionReset), SourceError::Network(_)));
+        assert!(matches!(
+            FtpSource::classify("context", FtpError::SecureError("handshake".to_string())),
+            SourceError::Other(_)
+        ));
+    }
+}
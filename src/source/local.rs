@@ -0,0 +1,72 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::{InventorySource, SourceError};
+
+const THAUM_EXTENSION: &str = ".thaum";
+const USER_CACHE_FILE_NAME: &str = "usercache.json";
+
+/// Reads `<username>.thaum` and `<uuid>.dat` files from a local player data directory.
+pub struct LocalSource {
+    dir: PathBuf,
+}
+
+impl LocalSource {
+    pub fn new(dir: PathBuf) -> Self {
+        LocalSource { dir }
+    }
+}
+
+impl LocalSource {
+    fn thaum_path(&self, username: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", username, THAUM_EXTENSION))
+    }
+
+    fn classify(context: &str, path: &Path, error: io::Error) -> SourceError {
+        let message = format!("{} '{}': {}", context, path.display(), error);
+        match error.kind() {
+            io::ErrorKind::NotFound => SourceError::NotFound(message),
+            _ => SourceError::Other(message),
+        }
+    }
+}
+
+impl InventorySource for LocalSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        let path = self.thaum_path(username);
+        fs::read(&path).map_err(|error| LocalSource::classify("Could not read", &path, error))
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        let path = self.dir.join(format!("{}.dat", uuid));
+        fs::read(&path).map_err(|error| LocalSource::classify("Could not read", &path, error))
+    }
+
+    /// The player data directory is `<server>/<world>/playerdata`, so the cache is two levels up.
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        let path = self.dir.join("..").join("..").join(USER_CACHE_FILE_NAME);
+        fs::read(&path).map_err(|error| LocalSource::classify("Could not read", &path, error))
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        let entries = fs::read_dir(&self.dir).map_err(|error| LocalSource::classify("Could not list", &self.dir, error))?;
+        let mut players: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(THAUM_EXTENSION).map(str::to_owned))
+            .collect();
+        players.sort();
+
+        Ok(players)
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        let path = self.thaum_path(username);
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|error| LocalSource::classify("Could not get modification time of", &path, error))?;
+
+        Ok(Some(modified))
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use nbt::{Blob, Value};
+
+use super::{InventorySource, SourceError};
+use crate::aspect::Aspect;
+
+/// In-memory source for tests. Clones share their files, so a test can keep a handle on a source it gave away
+/// and change files, queue failures or count reads afterwards.
+#[derive(Clone, Default)]
+pub struct MemorySource {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    thaum_files: HashMap<String, Vec<u8>>,
+    player_dats: HashMap<String, Vec<u8>>,
+    user_cache: Option<Vec<u8>>,
+    /// Returned by the next calls instead of their result, one error per call
+    failures: VecDeque<SourceError>,
+    reads: usize,
+}
+
+impl MemorySource {
+    /// A gzip compressed `.thaum` file holding the given aspect amounts and completed research.
+    pub fn thaum_file(aspects: &[(Aspect, i16)], research: &[&str]) -> Vec<u8> {
+        let aspects = aspects
+            .iter()
+            .map(|&(aspect, amount)| MemorySource::compound(vec![("key", Value::String(aspect.key())), ("amount", Value::Short(amount))]))
+            .collect();
+
+        MemorySource::gzip(vec![
+            ("THAUMCRAFT.ASPECTS", Value::List(aspects)),
+            ("THAUMCRAFT.RESEARCH", MemorySource::research_list(research)),
+        ])
+    }
+
+    /// A gzip compressed ThaumCraft 6 `<uuid>.dat` file knowing the given research.
+    pub fn tc6_dat(research: &[&str]) -> Vec<u8> {
+        let knowledge = MemorySource::compound(vec![("research", MemorySource::research_list(research))]);
+        MemorySource::gzip(vec![("ForgeCaps", MemorySource::compound(vec![("thaumcraft:knowledge", knowledge)]))])
+    }
+
+    fn compound(entries: Vec<(&str, Value)>) -> Value {
+        Value::Compound(entries.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    fn research_list(research: &[&str]) -> Value {
+        Value::List(research.iter().map(|&key| MemorySource::compound(vec![("key", Value::String(key.to_string()))])).collect())
+    }
+
+    fn gzip(entries: Vec<(&str, Value)>) -> Vec<u8> {
+        let mut blob = Blob::new();
+        for (name, value) in entries {
+            blob.insert(name, value).unwrap();
+        }
+
+        let mut content = Vec::new();
+        blob.to_gzip_writer(&mut content).unwrap();
+        content
+    }
+
+    pub fn set_thaum(&self, username: &str, content: Vec<u8>) {
+        self.state.lock().unwrap().thaum_files.insert(username.to_string(), content);
+    }
+
+    pub fn set_player_dat(&self, uuid: &str, content: Vec<u8>) {
+        self.state.lock().unwrap().player_dats.insert(uuid.to_string(), content);
+    }
+
+    pub fn set_user_cache(&self, content: &str) {
+        self.state.lock().unwrap().user_cache = Some(content.as_bytes().to_vec());
+    }
+
+    /// Makes the next call fail with the error, queued after earlier failures.
+    pub fn fail_next(&self, error: SourceError) {
+        self.state.lock().unwrap().failures.push_back(error);
+    }
+
+    /// Number of calls answered so far, failed ones included.
+    pub fn reads(&self) -> usize {
+        self.state.lock().unwrap().reads
+    }
+
+    fn answer<T>(&self, read: impl FnOnce(&MemoryState) -> Result<T, SourceError>) -> Result<T, SourceError> {
+        let mut state = self.state.lock().unwrap();
+        state.reads += 1;
+        match state.failures.pop_front() {
+            Some(error) => Err(error),
+            None => read(&state),
+        }
+    }
+
+    fn find(files: &HashMap<String, Vec<u8>>, name: &str) -> Result<Vec<u8>, SourceError> {
+        files.get(name).cloned().ok_or_else(|| SourceError::NotFound(format!("No file for '{}'", name)))
+    }
+}
+
+impl InventorySource for MemorySource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        self.answer(|state| MemorySource::find(&state.thaum_files, username))
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        self.answer(|state| {
+            let mut players: Vec<String> = state.thaum_files.keys().cloned().collect();
+            players.sort();
+            Ok(players)
+        })
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        self.answer(|state| MemorySource::find(&state.player_dats, uuid))
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.answer(|state| state.user_cache.clone().ok_or_else(|| SourceError::NotFound("No usercache.json".to_string())))
+    }
+
+    /// Only answers whether the file exists, callers have to read it to find out whether it changed.
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        self.answer(|state| MemorySource::find(&state.thaum_files, username).map(|_| None))
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use super::{Connect, FtpSource, InventorySource, Protocol, RemoteConfig, SftpSource, SourceError};
+
+/// Wraps the FTP or SFTP source, reconnecting and retrying with exponential backoff when the network fails.
+/// Authentication failures and missing files are returned right away.
+pub struct RetryingSource {
+    connect: Connect,
+    retries: u32,
+    first_backoff: Duration,
+    inner: Option<Box<dyn InventorySource>>,
+}
+
+impl RetryingSource {
+    const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+    /// Connects right away, so wrong credentials or addresses are reported before anything else happens.
+    pub fn connect(config: RemoteConfig) -> Result<Self, SourceError> {
+        let retries = config.retries;
+        RetryingSource::new(retries, RetryingSource::FIRST_BACKOFF, Box::new(move || RetryingSource::connect_once(&config)))
+    }
+
+    fn new(retries: u32, first_backoff: Duration, connect: Connect) -> Result<Self, SourceError> {
+        let mut source = RetryingSource {
+            connect,
+            retries,
+            first_backoff,
+            inner: None,
+        };
+        source.with_retries(|_| Ok(()))?;
+
+        Ok(source)
+    }
+
+    fn connect_once(config: &RemoteConfig) -> Result<Box<dyn InventorySource>, SourceError> {
+        match config.protocol {
+            Protocol::Ftp | Protocol::Ftps => Ok(Box::new(FtpSource::connect(config)?)),
+            Protocol::Sftp => Ok(Box::new(SftpSource::connect(config)?)),
+        }
+    }
+
+    fn with_retries<T>(&mut self, mut operation: impl FnMut(&mut dyn InventorySource) -> Result<T, SourceError>) -> Result<T, SourceError> {
+        let mut backoff = self.first_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = match &mut self.inner {
+                Some(inner) => operation(inner.as_mut()),
+                None => (self.connect)().and_then(|inner| operation(self.inner.insert(inner).as_mut())),
+            };
+
+            match result {
+                Err(error) if error.is_retryable() && attempt < self.retries => {
+                    attempt += 1;
+                    self.inner = None;
+                    println!("Warning: {}", error);
+                    println!("Retrying in {}s ({}/{})", backoff.as_secs(), attempt, self.retries);
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl InventorySource for RetryingSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        self.with_retries(|inner| inner.read_thaum(username))
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        self.with_retries(|inner| inner.read_player_dat(uuid))
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.with_retries(|inner| inner.read_user_cache())
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        self.with_retries(|inner| inner.list_players())
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        self.with_retries(|inner| inner.modified(username))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::source::MemorySource;
+
+    /// A retrying source over the memory source without backoff, counting how often it connects.
+    fn retrying_source(retries: u32, server: &MemorySource) -> (Result<RetryingSource, SourceError>, Arc<AtomicUsize>) {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let (server, counter) = (server.clone(), Arc::clone(&connections));
+        let connect: Connect = Box::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(server.clone()) as Box<dyn InventorySource>)
+        });
+
+        (RetryingSource::new(retries, Duration::ZERO, connect), connections)
+    }
+
+    #[test]
+    fn network_failures_are_retried_on_a_new_connection() {
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"alice".to_vec());
+        let (source, connections) = retrying_source(3, &server);
+        let mut source = source.unwrap();
+
+        server.fail_next(SourceError::Network("timed out".to_string()));
+        server.fail_next(SourceError::Network("connection reset".to_string()));
+        assert_eq!(source.read_thaum("alice"), Ok(b"alice".to_vec()));
+        assert_eq!(server.reads(), 3);
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retries_give_up_with_the_last_error() {
+        let server = MemorySource::default();
+        server.set_thaum("alice", b"alice".to_vec());
+        let mut source = retrying_source(2, &server).0.unwrap();
+
+        for _ in 0..3 {
+            server.fail_next(SourceError::Network("timed out".to_string()));
+        }
+        server.fail_next(SourceError::Network("still down".to_string()));
+        assert_eq!(source.read_thaum("alice"), Err(SourceError::Network("timed out".to_string())));
+        assert_eq!(server.reads(), 3);
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let server = MemorySource::default();
+        let (source, connections) = retrying_source(3, &server);
+        let mut source = source.unwrap();
+
+        server.fail_next(SourceError::Auth("wrong password".to_string()));
+        assert!(matches!(source.list_players(), Err(SourceError::Auth(_))));
+        assert!(matches!(source.read_thaum("nobody"), Err(SourceError::NotFound(_))));
+        assert_eq!(server.reads(), 2);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn connecting_is_retried_before_giving_up() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let connect: Connect = Box::new(move || match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(SourceError::Network("connection refused".to_string())),
+            _ => Ok(Box::new(MemorySource::default()) as Box<dyn InventorySource>),
+        });
+        assert!(RetryingSource::new(1, Duration::ZERO, connect).is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        let connect: Connect = Box::new(|| Err(SourceError::Auth("wrong password".to_string())));
+        assert!(matches!(RetryingSource::new(3, Duration::ZERO, connect), Err(SourceError::Auth(_))));
+    }
+}
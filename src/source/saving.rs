@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{InventorySource, SourceError};
+use crate::rcon::{RconClient, RconConfig};
+
+/// Asks the server to `save-all` over RCON before reading, because ThaumCraft only writes the `.thaum`
+/// files on save or logout. A failed save is only a warning, the possibly outdated files are still read.
+pub struct SavingSource {
+    inner: Box<dyn InventorySource>,
+    rcon: RconConfig,
+    last_save: Option<Instant>,
+}
+
+impl SavingSource {
+    /// Reads right after a save do not trigger another one, so loading a whole team saves only once.
+    const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+    const SAVE_COMMAND: &'static str = "save-all";
+
+    pub fn new(inner: Box<dyn InventorySource>, rcon: RconConfig) -> Self {
+        SavingSource { inner, rcon, last_save: None }
+    }
+
+    fn save_if_due(&mut self) {
+        if self.last_save.is_some_and(|last_save| last_save.elapsed() < SavingSource::SAVE_INTERVAL) {
+            return;
+        }
+
+        match RconClient::connect(&self.rcon).and_then(|mut client| client.command(SavingSource::SAVE_COMMAND)) {
+            Ok(response) => println!("Server saved over RCON: {}", response.lines().map(str::trim).collect::<Vec<_>>().join(" ")),
+            Err(error) => println!("Warning: could not save the server over RCON, player data may be outdated: {}", error),
+        }
+        self.last_save = Some(Instant::now());
+    }
+}
+
+impl InventorySource for SavingSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        self.save_if_due();
+        self.inner.read_thaum(username)
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        self.save_if_due();
+        self.inner.read_player_dat(uuid)
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        self.inner.read_user_cache()
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        self.save_if_due();
+        self.inner.list_players()
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        self.save_if_due();
+        self.inner.modified(username)
+    }
+}
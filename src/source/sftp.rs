@@ -0,0 +1,288 @@
+use std::{
+    io::Read,
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+
+use super::{InventorySource, RemoteConfig, SourceError};
+use crate::layout::PlayerDataLayout;
+
+const DEFAULT_SSH_PORT: u16 = 22;
+const SFTP_NO_SUCH_FILE: i32 = 2;
+const SFTP_NO_SUCH_PATH: i32 = 10;
+
+/// Downloads the player files over SFTP.
+pub struct SftpSource {
+    sftp: Sftp,
+    layout: PlayerDataLayout,
+}
+
+impl SftpSource {
+    /// Connects and authenticates to an SSH server, using the key file when given and the password otherwise.
+    /// With a key file, the password is used as the key passphrase.
+    pub fn connect(config: &RemoteConfig) -> Result<Self, SourceError> {
+        let known_hosts_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".ssh").join("known_hosts"));
+        SftpSource::connect_checking(config, known_hosts_path.as_deref())
+    }
+
+    fn connect_checking(config: &RemoteConfig, known_hosts_path: Option<&Path>) -> Result<Self, SourceError> {
+        let address = &config.address;
+        let (host, port) = super::host_and_port(address, DEFAULT_SSH_PORT)?;
+        let socket_address = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|error| SourceError::Network(format!("Could not resolve '{}': {}", address, error)))?
+            .next()
+            .ok_or_else(|| SourceError::Network(format!("Could not resolve '{}'", address)))?;
+        let tcp_stream = TcpStream::connect_timeout(&socket_address, config.timeout).map_err(|error| SourceError::Network(format!("Could not connect to SFTP: {}", error)))?;
+
+        let mut session = Session::new().map_err(|error| SourceError::Other(format!("Could not create SSH session: {}", error)))?;
+        session.set_tcp_stream(tcp_stream);
+        session.set_timeout(u32::try_from(config.timeout.as_millis()).unwrap_or(u32::MAX));
+        session
+            .handshake()
+            .map_err(|error| SourceError::Network(format!("Could not complete SSH handshake: {}", error)))?;
+        if let Some(known_hosts_path) = known_hosts_path {
+            SftpSource::check_known_host(&session, &host, port, known_hosts_path)?;
+        }
+
+        let password = config.password.as_deref();
+        match config.ssh_key.as_deref() {
+            Some(key_file) => session.userauth_pubkey_file(&config.username, None, key_file, password),
+            None => session.userauth_password(&config.username, password.ok_or_else(|| SourceError::Other("SFTP password is not set".to_string()))?),
+        }
+        .map_err(|error| SourceError::Auth(format!("Could not login to SFTP: {}", error)))?;
+
+        let sftp = session.sftp().map_err(|error| SftpSource::classify("Could not open SFTP channel", error))?;
+        let layout =
+            PlayerDataLayout::resolve(config.world_dir.as_deref(), &config.path_template, |path| SftpSource::download_file(&sftp, path).ok()).map_err(SourceError::Other)?;
+
+        Ok(SftpSource { sftp, layout })
+    }
+
+    fn download_file(sftp: &Sftp, path: &str) -> Result<Vec<u8>, SourceError> {
+        let mut content = Vec::new();
+        let mut file = sftp
+            .open(Path::new(path))
+            .map_err(|error| SftpSource::classify(&format!("Could not open '{}' on SFTP", path), error))?;
+        file.read_to_end(&mut content)
+            .map_err(|error| SourceError::Network(format!("Could not retrieve '{}' from SFTP: {}", path, error)))?;
+
+        Ok(content)
+    }
+
+    /// Missing files are reported by the SFTP subsystem, while session errors mean the connection broke or timed out.
+    fn classify(context: &str, error: ssh2::Error) -> SourceError {
+        let message = format!("{}: {}", context, error);
+        match error.code() {
+            ErrorCode::SFTP(SFTP_NO_SUCH_FILE | SFTP_NO_SUCH_PATH) => SourceError::NotFound(message),
+            ErrorCode::Session(_) => SourceError::Network(message),
+            ErrorCode::SFTP(_) => SourceError::Other(message),
+        }
+    }
+
+    /// Refuses to continue if the server key does not match `~/.ssh/known_hosts`, and warns about unknown servers.
+    fn check_known_host(session: &Session, host: &str, port: u16, known_hosts_path: &Path) -> Result<(), SourceError> {
+        let Some((host_key, _)) = session.host_key() else {
+            return Ok(());
+        };
+
+        let mut known_hosts = session
+            .known_hosts()
+            .map_err(|error| SourceError::Other(format!("Could not create known hosts list: {}", error)))?;
+        if known_hosts.read_file(known_hosts_path, KnownHostFileKind::OpenSSH).is_err() {
+            println!("Warning: could not read {}, the SFTP server identity is not verified", known_hosts_path.display());
+            return Ok(());
+        }
+
+        match known_hosts.check_port(host, port, host_key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::NotFound => {
+                println!("Warning: {} is not in known_hosts, the SFTP server identity is not verified", host);
+                Ok(())
+            }
+            CheckResult::Mismatch => Err(SourceError::Auth(format!("Host key of {} does not match known_hosts", host))),
+            CheckResult::Failure => Err(SourceError::Other(format!("Could not check host key of {}", host))),
+        }
+    }
+}
+
+impl InventorySource for SftpSource {
+    fn read_thaum(&mut self, username: &str) -> Result<Vec<u8>, SourceError> {
+        SftpSource::download_file(&self.sftp, &self.layout.thaum_path(username))
+    }
+
+    fn read_player_dat(&mut self, uuid: &str) -> Result<Vec<u8>, SourceError> {
+        SftpSource::download_file(&self.sftp, &self.layout.player_dat_path(uuid))
+    }
+
+    fn read_user_cache(&mut self) -> Result<Vec<u8>, SourceError> {
+        SftpSource::download_file(&self.sftp, PlayerDataLayout::USER_CACHE_PATH)
+    }
+
+    fn list_players(&mut self) -> Result<Vec<String>, SourceError> {
+        let dir = self.layout.player_data_dir();
+        let entries = self
+            .sftp
+            .readdir(Path::new(dir))
+            .map_err(|error| SftpSource::classify(&format!("Could not list '{}' on SFTP", dir), error))?;
+        let mut players: Vec<String> = entries
+            .iter()
+            .filter_map(|(path, _)| self.layout.username_from_file_name(path.file_name()?.to_str()?))
+            .map(str::to_owned)
+            .collect();
+        players.sort();
+
+        Ok(players)
+    }
+
+    fn modified(&mut self, username: &str) -> Result<Option<SystemTime>, SourceError> {
+        let path = self.layout.thaum_path(username);
+        let stat = self
+            .sftp
+            .stat(Path::new(&path))
+            .map_err(|error| SftpSource::classify(&format!("Could not get modification time of '{}' from SFTP", path), error))?;
+
+        Ok(stat.mtime.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        net::TcpListener,
+        process::{self, Child, Command},
+        thread,
+    };
+
+    use super::*;
+    use crate::source::{FtpMode, Protocol};
+
+    #[test]
+    fn classify_separates_missing_files_from_broken_sessions() {
+        let classify = |code| SftpSource::classify("context", ssh2::Error::new(code, "message"));
+
+        assert!(matches!(classify(ErrorCode::SFTP(SFTP_NO_SUCH_FILE)), SourceError::NotFound(_)));
+        assert!(matches!(classify(ErrorCode::SFTP(SFTP_NO_SUCH_PATH)), SourceError::NotFound(_)));
+        // LIBSSH2_ERROR_SOCKET_RECV and LIBSSH2_ERROR_TIMEOUT
+        assert!(matches!(classify(ErrorCode::Session(-43)), SourceError::Network(_)));
+        assert!(matches!(classify(ErrorCode::Session(-9)), SourceError::Network(_)));
+        // SSH_FX_PERMISSION_DENIED
+        assert!(matches!(classify(ErrorCode::SFTP(3)), SourceError::Other(_)));
+    }
+
+    /// A throwaway `sshd` on a free local port, accepting the client key for the current user, stopped on drop.
+    struct LocalSshServer {
+        dir: PathBuf,
+        port: u16,
+        process: Child,
+    }
+
+    impl LocalSshServer {
+        const PASSPHRASE: &'static str = "secret";
+
+        fn start(name: &str) -> LocalSshServer {
+            let dir = std::env::temp_dir().join(format!("{}-{}-sshd-{}", env!("CARGO_PKG_NAME"), process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("World").join("playerdata")).unwrap();
+            for (key, passphrase) in [("host_key", ""), ("client_key", LocalSshServer::PASSPHRASE)] {
+                let status = Command::new("ssh-keygen")
+                    .args(["-q", "-t", "ed25519", "-N", passphrase, "-f"])
+                    .arg(dir.join(key))
+                    .status()
+                    .unwrap();
+                assert!(status.success(), "ssh-keygen failed");
+            }
+            fs::copy(dir.join("client_key.pub"), dir.join("authorized_keys")).unwrap();
+
+            let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let config = format!(
+                "Port {}\nListenAddress 127.0.0.1\nHostKey {}\nAuthorizedKeysFile {}\nPidFile none\nStrictModes no\nUsePAM no\n\
+                 PasswordAuthentication yes\nSubsystem sftp internal-sftp\nLogLevel ERROR\n",
+                port,
+                dir.join("host_key").display(),
+                dir.join("authorized_keys").display()
+            );
+            fs::write(dir.join("sshd_config"), config).unwrap();
+
+            let sshd = std::env::var("THAUM_TEST_SSHD").unwrap_or_else(|_| "/usr/sbin/sshd".to_string());
+            let process = Command::new(sshd).arg("-D").arg("-e").arg("-f").arg(dir.join("sshd_config")).spawn().unwrap();
+            for _ in 0..50 {
+                if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+
+            LocalSshServer { dir, port, process }
+        }
+
+        fn config(&self, password: &str, ssh_key: bool) -> RemoteConfig {
+            RemoteConfig {
+                protocol: Protocol::Sftp,
+                address: format!("127.0.0.1:{}", self.port),
+                username: std::env::var("USER").unwrap_or_else(|_| "root".to_string()),
+                password: Some(password.to_string()),
+                ssh_key: ssh_key.then(|| self.dir.join("client_key")),
+                tls_pinned_cert: None,
+                world_dir: Some(self.dir.join("World").display().to_string()),
+                path_template: PlayerDataLayout::DEFAULT_TEMPLATE.to_string(),
+                ftp_mode: FtpMode::Passive,
+                timeout: Duration::from_secs(5),
+                retries: 0,
+            }
+        }
+
+        /// A known_hosts file listing the given public key for the server.
+        fn known_hosts(&self, public_key_file: &str) -> PathBuf {
+            let public_key = fs::read_to_string(self.dir.join(public_key_file)).unwrap();
+            let path = self.dir.join(format!("known_hosts_{}", public_key_file));
+            fs::write(&path, format!("[127.0.0.1]:{} {}", self.port, public_key)).unwrap();
+            path
+        }
+    }
+
+    impl Drop for LocalSshServer {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Needs OpenSSH's `sshd`, at `/usr/sbin/sshd` or in `THAUM_TEST_SSHD`: `cargo test -- --ignored local_ssh_server`.
+    #[test]
+    #[ignore = "needs an OpenSSH sshd binary"]
+    fn reads_from_a_local_ssh_server() {
+        let server = LocalSshServer::start("read");
+        let thaum = b"player data, read back byte for byte".to_vec();
+        fs::write(server.dir.join("World").join("playerdata").join("alice.thaum"), &thaum).unwrap();
+
+        // The password unlocks the key file
+        let mut source = SftpSource::connect_checking(&server.config(LocalSshServer::PASSPHRASE, true), None).unwrap();
+        assert_eq!(source.read_thaum("alice").unwrap(), thaum);
+        assert!(matches!(source.read_thaum("bob"), Err(SourceError::NotFound(_))));
+        assert_eq!(source.list_players().unwrap(), vec!["alice".to_string()]);
+        assert!(source.modified("alice").unwrap().is_some());
+
+        assert!(matches!(SftpSource::connect_checking(&server.config("wrong", true), None), Err(SourceError::Auth(_))));
+        assert!(matches!(SftpSource::connect_checking(&server.config("wrong", false), None), Err(SourceError::Auth(_))));
+    }
+
+    #[test]
+    #[ignore = "needs an OpenSSH sshd binary"]
+    fn checks_the_local_ssh_server_against_known_hosts() {
+        let server = LocalSshServer::start("known-hosts");
+        let config = server.config(LocalSshServer::PASSPHRASE, true);
+
+        assert!(SftpSource::connect_checking(&config, Some(&server.known_hosts("host_key.pub"))).is_ok());
+        assert!(SftpSource::connect_checking(&config, Some(&server.dir.join("missing_known_hosts"))).is_ok());
+        match SftpSource::connect_checking(&config, Some(&server.known_hosts("client_key.pub"))) {
+            Err(SourceError::Auth(message)) => assert!(message.contains("does not match known_hosts"), "{}", message),
+            other => panic!("expected a host key mismatch, got {:?}", other.map(|_| ())),
+        }
+    }
+}
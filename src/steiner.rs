@@ -0,0 +1,539 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use crate::{
+    aspect::Aspect,
+    solver::{AspectPath, Solver},
+    table::CostTable,
+};
+
+/// Minimal hex distance between two terminals on the note, their chain needs at least `distance + 1` aspects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DistanceConstraint {
+    pub a: Aspect,
+    pub b: Aspect,
+    pub distance: u8,
+}
+
+/// A network of aspect chains connecting all terminals, every node being one aspect on the note.
+#[derive(Debug, Clone)]
+pub struct SteinerTree {
+    /// Aspect of every node, the terminals first. The same aspect can appear on several nodes.
+    pub nodes: Vec<Aspect>,
+    pub edges: Vec<(usize, usize)>,
+    pub terminal_count: usize,
+    /// Expected price of all nodes that are not terminals
+    pub price: u32,
+    /// Whether the tree is proven cheapest, false when the distance constraints required the pairwise heuristic
+    pub exact: bool,
+    pub unmet_constraints: Vec<DistanceConstraint>,
+}
+
+impl SteinerTree {
+    fn new(terminals: &[Aspect]) -> Self {
+        SteinerTree {
+            nodes: terminals.to_vec(),
+            edges: Vec::new(),
+            terminal_count: terminals.len(),
+            price: 0,
+            exact: true,
+            unmet_constraints: Vec::new(),
+        }
+    }
+
+    /// Adds the intermediate aspects of a path between two existing nodes as new nodes.
+    fn add_chain(&mut self, from: usize, to: usize, path: &[Aspect]) {
+        let mut previous = from;
+        for &aspect in &path[1..path.len() - 1] {
+            self.nodes.push(aspect);
+            self.edges.push((previous, self.nodes.len() - 1));
+            previous = self.nodes.len() - 1;
+        }
+        self.edges.push((previous, to));
+    }
+
+    /// Number of placed aspects, the terminals are already on the note.
+    pub fn placed_count(&self) -> usize {
+        self.nodes.len() - self.terminal_count
+    }
+
+    fn adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adjacency = vec![Vec::new(); self.nodes.len()];
+        for &(a, b) in &self.edges {
+            adjacency[a].push(b);
+            adjacency[b].push(a);
+        }
+
+        adjacency
+    }
+
+    /// The tree split into chains between terminals and branching nodes, both ends included.
+    pub fn chains(&self) -> Vec<AspectPath> {
+        let adjacency = self.adjacency();
+        let is_key = |node: usize| node < self.terminal_count || adjacency[node].len() != 2;
+
+        let mut chains = Vec::new();
+        for start in (0..self.nodes.len()).filter(|&node| is_key(node)) {
+            for &first in &adjacency[start] {
+                let mut chain = vec![start, first];
+                while !is_key(*chain.last().unwrap()) {
+                    let [current, previous] = [chain[chain.len() - 1], chain[chain.len() - 2]];
+                    let next = adjacency[current].iter().copied().find(|&node| node != previous).unwrap();
+                    chain.push(next);
+                }
+                // Every chain is found from both of its ends
+                if start < *chain.last().unwrap() {
+                    chains.push(chain.iter().map(|&node| self.nodes[node]).collect());
+                }
+            }
+        }
+
+        chains
+    }
+
+    /// Number of aspects on the tree path between two nodes, both ends included.
+    fn path_length(&self, from: usize, to: usize) -> Option<usize> {
+        let adjacency = self.adjacency();
+        let mut lengths = vec![None; self.nodes.len()];
+        lengths[from] = Some(1);
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            for &neighbour in &adjacency[node] {
+                if lengths[neighbour].is_none() {
+                    lengths[neighbour] = lengths[node].map(|length| length + 1);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        lengths[to]
+    }
+}
+
+/// How the cheapest tree rooted at a node was reached for a set of terminals.
+#[derive(Debug, Clone, Copy)]
+enum Origin {
+    Unreached,
+    Terminal,
+    /// Two trees of the given subset and the rest of the terminals joined at the node
+    Merged(usize),
+    /// The tree rooted at the given neighbouring node extended by one step
+    Extended(usize),
+}
+
+/// Graph with prices on the nodes instead of the edges, `None` marking nodes that cannot be used.
+pub struct NodeWeightedGraph {
+    pub prices: Vec<Option<u32>>,
+    pub neighbours: Vec<Vec<usize>>,
+}
+
+impl NodeWeightedGraph {
+    /// Dreyfus-Wagner: `costs[subset][node]` is the cheapest tree connecting the subset of terminals and the node,
+    /// built by merging trees of smaller subsets at the node and then extending them step by step like Dijkstra.
+    /// Returns the `(parent, child)` edges of the tree in breadth-first order from the first terminal.
+    pub fn cheapest_tree(&self, terminals: &[usize]) -> Option<Vec<(usize, usize)>> {
+        let node_count = self.prices.len();
+        let full_set = (1 << terminals.len()) - 1;
+        let mut costs = vec![vec![u32::MAX; node_count]; full_set + 1];
+        let mut origins = vec![vec![Origin::Unreached; node_count]; full_set + 1];
+        for (bit, &terminal) in terminals.iter().enumerate() {
+            costs[1 << bit][terminal] = self.prices[terminal]?;
+            origins[1 << bit][terminal] = Origin::Terminal;
+        }
+
+        for set in 1..=full_set {
+            let lowest_bit = set & set.wrapping_neg();
+            for node in 0..node_count {
+                let Some(price) = self.prices[node] else {
+                    continue;
+                };
+                // Every split once, the part holding the lowest terminal first
+                let mut subset = (set - 1) & set;
+                while subset > 0 {
+                    let (cost_a, cost_b) = (costs[subset][node], costs[set ^ subset][node]);
+                    if subset & lowest_bit != 0 && cost_a != u32::MAX && cost_b != u32::MAX && cost_a + cost_b - price < costs[set][node] {
+                        costs[set][node] = cost_a + cost_b - price;
+                        origins[set][node] = Origin::Merged(subset);
+                    }
+                    subset = (subset - 1) & set;
+                }
+            }
+
+            let mut queue: BinaryHeap<Reverse<(u32, usize)>> = (0..node_count)
+                .filter(|&node| costs[set][node] != u32::MAX)
+                .map(|node| Reverse((costs[set][node], node)))
+                .collect();
+            while let Some(Reverse((cost, node))) = queue.pop() {
+                if cost > costs[set][node] {
+                    continue;
+                }
+                for &neighbour in &self.neighbours[node] {
+                    let Some(price) = self.prices[neighbour] else {
+                        continue;
+                    };
+                    if cost.saturating_add(price) < costs[set][neighbour] {
+                        costs[set][neighbour] = cost + price;
+                        origins[set][neighbour] = Origin::Extended(node);
+                        queue.push(Reverse((cost + price, neighbour)));
+                    }
+                }
+            }
+        }
+
+        let root = terminals[0];
+        if costs[full_set][root] == u32::MAX {
+            return None;
+        }
+
+        let mut edges = BTreeSet::new();
+        let mut stack = vec![(full_set, root)];
+        while let Some((set, node)) = stack.pop() {
+            match origins[set][node] {
+                Origin::Unreached | Origin::Terminal => {}
+                Origin::Merged(subset) => stack.extend([(subset, node), (set ^ subset, node)]),
+                Origin::Extended(previous) => {
+                    edges.insert((previous.min(node), previous.max(node)));
+                    stack.push((set, previous));
+                }
+            }
+        }
+
+        // Free nodes can let merged trees overlap, so keep a spanning tree of the edges found
+        let mut tree_edges = Vec::new();
+        let mut reached = HashSet::from([root]);
+        let mut queue = VecDeque::from([root]);
+        while let Some(node) = queue.pop_front() {
+            for &(a, b) in &edges {
+                let next = match (a == node, b == node) {
+                    (true, _) => b,
+                    (_, true) => a,
+                    _ => continue,
+                };
+                if reached.insert(next) {
+                    tree_edges.push((node, next));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Some(tree_edges)
+    }
+}
+
+/// Finds the cheapest network connecting a set of terminal aspects, the intermediate aspects priced like
+/// [`Solver::step_price`]. Chains may share intermediate aspects by branching from them.
+pub struct SteinerSolver<'a> {
+    solver: &'a Solver,
+    terminals: Vec<Aspect>,
+    constraints: Vec<DistanceConstraint>,
+}
+
+impl<'a> SteinerSolver<'a> {
+    /// The exact search grows with `3^terminals`, notes never have more starting aspects than this.
+    pub const MAX_TERMINALS: usize = 10;
+
+    /// Terminals given more than once are only connected once.
+    pub fn new(solver: &'a Solver, terminals: &[Aspect], constraints: Vec<DistanceConstraint>) -> Result<Self, String> {
+        let mut unique_terminals: Vec<Aspect> = Vec::new();
+        for &terminal in terminals {
+            if !unique_terminals.contains(&terminal) {
+                unique_terminals.push(terminal);
+            }
+        }
+        if !(2..=SteinerSolver::MAX_TERMINALS).contains(&unique_terminals.len()) {
+            return Err(format!("Between 2 and {} different aspects can be connected", SteinerSolver::MAX_TERMINALS));
+        }
+        for constraint in &constraints {
+            if !unique_terminals.contains(&constraint.a) || !unique_terminals.contains(&constraint.b) {
+                return Err(format!(
+                    "Distance constraint between {:?} and {:?} uses an aspect that is not connected",
+                    constraint.a, constraint.b
+                ));
+            }
+            if constraint.a == constraint.b {
+                return Err(format!("{:?} cannot keep a distance to itself", constraint.a));
+            }
+            if constraint.distance >= CostTable::MAX_LENGTH {
+                return Err(format!("Distance {} is larger than any research note", constraint.distance));
+            }
+        }
+
+        Ok(SteinerSolver {
+            solver,
+            terminals: unique_terminals,
+            constraints,
+        })
+    }
+
+    /// Runs the exact search first. When its tree puts two terminals closer than their constraint allows,
+    /// falls back to joining pairwise chains of sufficient length along a minimum spanning tree.
+    pub fn solve(&self) -> Option<SteinerTree> {
+        let tree = self.dreyfus_wagner()?;
+        if self.unmet_constraints(&tree).is_empty() {
+            return Some(tree);
+        }
+
+        let mut tree = self.connect_pairwise()?;
+        tree.exact = false;
+        tree.unmet_constraints = self.unmet_constraints(&tree);
+        Some(tree)
+    }
+
+    fn unmet_constraints(&self, tree: &SteinerTree) -> Vec<DistanceConstraint> {
+        let node_of = |aspect: Aspect| self.terminals.iter().position(|&terminal| terminal == aspect).unwrap();
+        self.constraints
+            .iter()
+            .filter(|constraint| {
+                tree.path_length(node_of(constraint.a), node_of(constraint.b))
+                    .is_none_or(|length| length <= constraint.distance as usize)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Minimal number of aspects on the chain between two terminals.
+    fn min_length(&self, a: Aspect, b: Aspect) -> u8 {
+        self.constraints
+            .iter()
+            .filter(|constraint| (constraint.a, constraint.b) == (a, b) || (constraint.a, constraint.b) == (b, a))
+            .map(|constraint| constraint.distance + 1)
+            .fold(2, u8::max)
+    }
+
+    fn dreyfus_wagner(&self) -> Option<SteinerTree> {
+        let aspects = self.solver.aspect_inventory().edition().aspects();
+        let index_of: HashMap<Aspect, usize> = aspects.iter().enumerate().map(|(index, &aspect)| (aspect, index)).collect();
+        let graph = NodeWeightedGraph {
+            // Terminals are already on the note, passing through them is free
+            prices: aspects
+                .iter()
+                .map(|&aspect| if self.terminals.contains(&aspect) { Some(0) } else { self.solver.step_price(aspect) })
+                .collect(),
+            neighbours: aspects
+                .iter()
+                .map(|&aspect| self.solver.neighbours(aspect).filter_map(|neighbour| index_of.get(&neighbour).copied()).collect())
+                .collect(),
+        };
+        let terminals: Vec<usize> = self.terminals.iter().map(|terminal| index_of[terminal]).collect();
+
+        let mut tree = SteinerTree::new(&self.terminals);
+        let mut node_of: HashMap<usize, usize> = terminals.iter().enumerate().map(|(node, &aspect)| (aspect, node)).collect();
+        for (parent, child) in graph.cheapest_tree(&terminals)? {
+            let child_node = *node_of.entry(child).or_insert_with(|| {
+                tree.nodes.push(aspects[child]);
+                tree.price += graph.prices[child].unwrap_or_default();
+                tree.nodes.len() - 1
+            });
+            tree.edges.push((node_of[&parent], child_node));
+        }
+
+        Some(tree)
+    }
+
+    /// Prim's algorithm over the terminals, the cost of a pair being its cheapest chain that respects the minimal distance.
+    fn connect_pairwise(&self) -> Option<SteinerTree> {
+        let path_tables: Vec<_> = self.terminals.iter().map(|&terminal| self.solver.find_paths(terminal, CostTable::MAX_LENGTH)).collect();
+        let cheapest_chain = |from: usize, to: usize| {
+            (self.min_length(self.terminals[from], self.terminals[to])..=CostTable::MAX_LENGTH)
+                .filter_map(|length| path_tables[from].price(self.terminals[to], length).map(|price| (length, price)))
+                .min_by_key(|&(length, price)| (price, length))
+        };
+
+        let mut tree = SteinerTree::new(&self.terminals);
+        let mut connected = vec![false; self.terminals.len()];
+        connected[0] = true;
+        for _ in 1..self.terminals.len() {
+            let (from, to, length, price) = (0..self.terminals.len())
+                .filter(|&from| connected[from])
+                .flat_map(|from| (0..self.terminals.len()).filter(|&to| !connected[to]).map(move |to| (from, to)))
+                .filter_map(|(from, to)| cheapest_chain(from, to).map(|(length, price)| (from, to, length, price)))
+                .min_by_key(|&(_, _, length, price)| (price, length))?;
+
+            let path = path_tables[from].paths(self.terminals[to], length).next()?;
+            tree.add_chain(from, to, &path);
+            tree.price += price;
+            connected[to] = true;
+        }
+
+        Some(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aspect::{AspectInventory, Edition},
+        reserve::Reserves,
+        skill::ResearchSkill,
+    };
+
+    /// Every aspect with a different amount, so that prices tell paths apart.
+    fn solver() -> Solver {
+        let inventory = Edition::Tc4
+            .aspects()
+            .into_iter()
+            .enumerate()
+            .map(|(index, aspect)| (aspect, (index * 7 % 23) as u16 + 1))
+            .collect();
+        Solver::new(AspectInventory::new(inventory, Edition::Tc4), Reserves::default(), ResearchSkill::None)
+    }
+
+    /// Small graphs from a fixed linear congruential sequence, some nodes unusable.
+    fn random_graphs() -> Vec<NodeWeightedGraph> {
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move |bound: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        (0..40)
+            .map(|_| {
+                let node_count = 9;
+                let prices = (0..node_count).map(|_| if next(8) == 0 { None } else { Some(next(20) as u32) }).collect();
+                let mut neighbours = vec![Vec::new(); node_count];
+                for a in 0..node_count {
+                    for b in a + 1..node_count {
+                        if next(3) == 0 {
+                            neighbours[a].push(b);
+                            neighbours[b].push(a);
+                        }
+                    }
+                }
+                NodeWeightedGraph { prices, neighbours }
+            })
+            .collect()
+    }
+
+    /// Price of the cheapest connected set of nodes holding all terminals, trying every set.
+    fn brute_force_price(graph: &NodeWeightedGraph, terminals: &[usize]) -> Option<u32> {
+        let node_count = graph.prices.len();
+        (0..1u32 << node_count)
+            .filter(|set| terminals.iter().all(|&terminal| set & (1 << terminal) != 0))
+            .filter(|set| (0..node_count).all(|node| set & (1 << node) == 0 || graph.prices[node].is_some()))
+            .filter(|&set| {
+                let mut reached = 1 << terminals[0];
+                let mut queue = vec![terminals[0]];
+                while let Some(node) = queue.pop() {
+                    for &neighbour in &graph.neighbours[node] {
+                        if set & (1 << neighbour) != 0 && reached & (1 << neighbour) == 0 {
+                            reached |= 1 << neighbour;
+                            queue.push(neighbour);
+                        }
+                    }
+                }
+                reached == set
+            })
+            .map(|set| (0..node_count).filter(|node| set & (1 << node) != 0).map(|node| graph.prices[node].unwrap()).sum())
+            .min()
+    }
+
+    /// Asserts that the edges are a tree of existing links over distinct nodes, reaching every terminal.
+    fn assert_spanning_tree(edges: &[(usize, usize)], terminals: &[usize], linked: impl Fn(usize, usize) -> bool) -> HashSet<usize> {
+        let mut nodes = HashSet::from([terminals[0]]);
+        for &(parent, child) in edges {
+            assert!(linked(parent, child), "{} and {} are not linked", parent, child);
+            assert!(nodes.contains(&parent), "{} is reached before its parent {}", child, parent);
+            assert!(nodes.insert(child), "{} is reached twice", child);
+        }
+        assert!(terminals.iter().all(|terminal| nodes.contains(terminal)));
+        nodes
+    }
+
+    #[test]
+    fn cheapest_trees_match_brute_force() {
+        let mut found = 0;
+        for graph in random_graphs() {
+            for terminals in [vec![0, 1], vec![0, 4, 8], vec![1, 3, 5, 7]] {
+                let expected = brute_force_price(&graph, &terminals);
+                let Some(edges) = graph.cheapest_tree(&terminals) else {
+                    assert_eq!(expected, None);
+                    continue;
+                };
+
+                let nodes = assert_spanning_tree(&edges, &terminals, |a, b| graph.neighbours[a].contains(&b));
+                let price: u32 = nodes.iter().map(|&node| graph.prices[node].unwrap()).sum();
+                assert_eq!(Some(price), expected, "terminals {:?}", terminals);
+                found += 1;
+            }
+        }
+        assert!(found > 40, "only {} graphs connect their terminals", found);
+    }
+
+    #[test]
+    fn two_terminals_cost_their_cheapest_path() {
+        let solver = solver();
+        for (a, b) in [(Aspect::Aer, Aspect::Ignis), (Aspect::Terra, Aspect::Lux), (Aspect::Ordo, Aspect::Perditio)] {
+            let tree = SteinerSolver::new(&solver, &[a, b], Vec::new()).unwrap().solve().unwrap();
+            let path_table = solver.find_paths(a, CostTable::MAX_LENGTH);
+            let cheapest = (2..=CostTable::MAX_LENGTH).filter_map(|length| path_table.price(b, length)).min();
+            assert_eq!(Some(tree.price), cheapest, "{:?} to {:?}", a, b);
+            assert!(tree.exact);
+        }
+    }
+
+    #[test]
+    fn trees_are_spanning_trees_of_linked_aspects() {
+        let solver = solver();
+        let terminals = [Aspect::Aer, Aspect::Terra, Aspect::Ordo, Aspect::Perditio];
+        let tree = SteinerSolver::new(&solver, &terminals, Vec::new()).unwrap().solve().unwrap();
+
+        assert_eq!(&tree.nodes[..tree.terminal_count], terminals);
+        assert_eq!(tree.edges.len(), tree.nodes.len() - 1);
+        let nodes = assert_spanning_tree(&tree.edges, &[0, 1, 2, 3], |a, b| solver.neighbours(tree.nodes[a]).any(|aspect| aspect == tree.nodes[b]));
+        assert_eq!(nodes.len(), tree.nodes.len());
+
+        let price: u32 = tree.nodes[tree.terminal_count..].iter().map(|&aspect| solver.step_price(aspect).unwrap()).sum();
+        assert_eq!(tree.price, price);
+        assert_eq!(tree.placed_count(), tree.nodes.len() - 4);
+    }
+
+    #[test]
+    fn violated_constraints_fall_back_to_pairwise_chains() {
+        let solver = solver();
+        let constraint = DistanceConstraint {
+            a: Aspect::Aer,
+            b: Aspect::Lux,
+            distance: 3,
+        };
+        let tree = SteinerSolver::new(&solver, &[Aspect::Aer, Aspect::Lux], vec![constraint]).unwrap().solve().unwrap();
+        assert!(!tree.exact);
+        assert!(tree.unmet_constraints.is_empty());
+        assert_eq!(tree.path_length(0, 1), Some(4));
+        assert_eq!(tree.chains().len(), 1);
+
+        // Chains are only kept apart between the pairs they join directly
+        let constraint = DistanceConstraint {
+            a: Aspect::Aer,
+            b: Aspect::Ignis,
+            distance: 5,
+        };
+        let tree = SteinerSolver::new(&solver, &[Aspect::Aer, Aspect::Lux, Aspect::Ignis], vec![constraint])
+            .unwrap()
+            .solve()
+            .unwrap();
+        assert!(!tree.exact);
+        assert_eq!(tree.unmet_constraints, [constraint]);
+    }
+
+    #[test]
+    fn new_rejects_impossible_requests() {
+        let solver = solver();
+        let constraint = |a, b, distance| DistanceConstraint { a, b, distance };
+        assert!(SteinerSolver::new(&solver, &[Aspect::Aer], Vec::new()).is_err());
+        assert!(SteinerSolver::new(&solver, &[Aspect::Aer, Aspect::Aer], Vec::new()).is_err());
+
+        let too_many = &Edition::Tc4.aspects()[..SteinerSolver::MAX_TERMINALS + 1];
+        assert!(SteinerSolver::new(&solver, &too_many[..SteinerSolver::MAX_TERMINALS], Vec::new()).is_ok());
+        assert!(SteinerSolver::new(&solver, too_many, Vec::new()).is_err());
+
+        let terminals = [Aspect::Aer, Aspect::Ignis];
+        let error = SteinerSolver::new(&solver, &terminals, vec![constraint(Aspect::Aer, Aspect::Aer, 3)]).err().unwrap();
+        assert_eq!(error, "Aer cannot keep a distance to itself");
+        assert!(SteinerSolver::new(&solver, &terminals, vec![constraint(Aspect::Aer, Aspect::Ordo, 3)]).is_err());
+        assert!(SteinerSolver::new(&solver, &terminals, vec![constraint(Aspect::Aer, Aspect::Ignis, CostTable::MAX_LENGTH)]).is_err());
+        assert!(SteinerSolver::new(&solver, &terminals, vec![constraint(Aspect::Aer, Aspect::Ignis, CostTable::MAX_LENGTH - 1)]).is_ok());
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{aspect::Aspect, solver::Solver};
+
+/// Cheapest price and number of cheapest paths for every start, end and length up to [`CostTable::MAX_LENGTH`],
+/// valid for the inventory, reserves and research skill of the solver it was built from.
+pub struct CostTable {
+    aspects: Vec<Aspect>,
+    index_of: HashMap<Aspect, usize>,
+    /// Price and path count by start, end and length from 2, [`CostTable::NO_PATH`] marking unconnectable pairs
+    entries: Vec<(u32, u64)>,
+}
+
+impl CostTable {
+    /// Longest path a research note can need, from one corner of the largest grid to the opposite one.
+    pub const MAX_LENGTH: u8 = 12;
+    const LENGTHS: usize = CostTable::MAX_LENGTH as usize - 1;
+    const NO_PATH: u32 = u32::MAX;
+
+    const MAGIC: &'static [u8; 4] = b"TRCT";
+    /// Bump whenever step prices or the file layout change, so old cache files are rebuilt.
+    const VERSION: u8 = 1;
+    const EXTENSION: &'static str = "table";
+
+    /// `<cache dir>/thaumcraft-research-solver/tables`, next to the cached inventories.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("tables"))
+    }
+
+    /// Runs the solver once from every aspect, each run covering all ends and lengths.
+    pub fn build(solver: &Solver) -> CostTable {
+        let aspects = solver.aspect_inventory().edition().aspects();
+        let mut entries = Vec::with_capacity(aspects.len() * aspects.len() * CostTable::LENGTHS);
+        for &start in &aspects {
+            let path_table = solver.find_paths(start, CostTable::MAX_LENGTH);
+            for &end in &aspects {
+                for length in 2..=CostTable::MAX_LENGTH {
+                    entries.push(match path_table.price(end, length) {
+                        Some(price) => (price, path_table.count(end, length)),
+                        None => (CostTable::NO_PATH, 0),
+                    });
+                }
+            }
+        }
+
+        CostTable::new(aspects, entries)
+    }
+
+    /// Reads the table of the solver's inventory from the cache directory, building and writing it when missing.
+    /// A cache that cannot be written only costs the rebuild next time, so it is a warning.
+    pub fn load_or_build(solver: &Solver, cache_dir: &Path) -> CostTable {
+        let path = cache_dir.join(format!("{:016x}.{}", CostTable::inventory_key(solver), CostTable::EXTENSION));
+        let aspects = solver.aspect_inventory().edition().aspects();
+        if let Some(cost_table) = fs::read(&path).ok().and_then(|content| CostTable::decode(aspects, &content)) {
+            return cost_table;
+        }
+
+        let cost_table = CostTable::build(solver);
+        if let Err(error) = fs::create_dir_all(cache_dir).and_then(|_| fs::write(&path, cost_table.encode())) {
+            println!("Warning: could not cache the cost table in '{}': {}", path.display(), error);
+        }
+
+        cost_table
+    }
+
+    fn new(aspects: Vec<Aspect>, entries: Vec<(u32, u64)>) -> CostTable {
+        let index_of = aspects.iter().enumerate().map(|(index, &aspect)| (aspect, index)).collect();
+        CostTable { aspects, index_of, entries }
+    }
+
+    pub fn aspects(&self) -> &[Aspect] {
+        &self.aspects
+    }
+
+    /// Price of the cheapest paths with the given number of aspects, `None` if there is none.
+    pub fn price(&self, start: Aspect, end: Aspect, length: u8) -> Option<u32> {
+        self.entry(start, end, length).map(|(price, _)| price)
+    }
+
+    /// Number of distinct paths sharing the cheapest price.
+    pub fn count(&self, start: Aspect, end: Aspect, length: u8) -> u64 {
+        self.entry(start, end, length).map(|(_, count)| count).unwrap_or_default()
+    }
+
+    fn entry(&self, start: Aspect, end: Aspect, length: u8) -> Option<(u32, u64)> {
+        if !(2..=CostTable::MAX_LENGTH).contains(&length) {
+            return None;
+        }
+        let index = (self.index_of.get(&start)? * self.aspects.len() + self.index_of.get(&end)?) * CostTable::LENGTHS + length as usize - 2;
+        self.entries.get(index).copied().filter(|&(price, _)| price != CostTable::NO_PATH)
+    }
+
+    /// Stable FNV-1a hash of everything the step prices depend on, naming the cache file.
+    fn inventory_key(solver: &Solver) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let aspect_inventory = solver.aspect_inventory();
+        let reserves = solver.reserves();
+        let mut description = format!(
+            "{:?} {:?} {:?} max={}",
+            aspect_inventory.edition(),
+            reserves.policy(),
+            solver.research_skill(),
+            aspect_inventory.max_amount()
+        );
+        for aspect in aspect_inventory.edition().aspects() {
+            description += &format!(" {}={}/{}", aspect.key(), aspect_inventory.display_amount(aspect), reserves.threshold_of(aspect));
+        }
+
+        description.bytes().fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+    }
+
+    /// `magic, version, aspect count (u16), then price (u32) and count (u64) of every entry`, in little endian.
+    fn encode(&self) -> Vec<u8> {
+        let mut content = Vec::with_capacity(7 + self.entries.len() * 12);
+        content.extend_from_slice(CostTable::MAGIC);
+        content.push(CostTable::VERSION);
+        content.extend_from_slice(&(self.aspects.len() as u16).to_le_bytes());
+        for &(price, count) in &self.entries {
+            content.extend_from_slice(&price.to_le_bytes());
+            content.extend_from_slice(&count.to_le_bytes());
+        }
+
+        content
+    }
+
+    /// Returns `None` for files of another version or edition, so they are rebuilt.
+    fn decode(aspects: Vec<Aspect>, content: &[u8]) -> Option<CostTable> {
+        let (header, body) = content.split_at_checked(7)?;
+        let aspect_count = u16::from_le_bytes([header[5], header[6]]) as usize;
+        if &header[..4] != CostTable::MAGIC || header[4] != CostTable::VERSION || aspect_count != aspects.len() {
+            return None;
+        }
+        if body.len() != aspect_count * aspect_count * CostTable::LENGTHS * 12 {
+            return None;
+        }
+
+        let entries = body
+            .chunks_exact(12)
+            .map(|entry| {
+                let (price, count) = entry.split_at(4);
+                (u32::from_le_bytes(price.try_into().unwrap()), u64::from_le_bytes(count.try_into().unwrap()))
+            })
+            .collect();
+
+        Some(CostTable::new(aspects, entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::{
+        aspect::{AspectInventory, Edition},
+        reserve::{ReservePolicy, Reserves},
+        skill::ResearchSkill,
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}-table-{}", env!("CARGO_PKG_NAME"), process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn solver(amounts: &[(Aspect, u16)], thresholds: &[(Aspect, u16)], policy: ReservePolicy, research_skill: ResearchSkill) -> Solver {
+        let mut inventory: HashMap<Aspect, u16> = Edition::Tc4.aspects().into_iter().map(|aspect| (aspect, 5)).collect();
+        inventory.remove(&Aspect::Vitreus);
+        inventory.extend(amounts.iter().copied());
+        let reserves = Reserves::new(thresholds.iter().copied().collect(), policy);
+
+        Solver::new(AspectInventory::new(inventory, Edition::Tc4), reserves, research_skill)
+    }
+
+    fn default_solver() -> Solver {
+        solver(&[(Aspect::Lux, 9)], &[], ReservePolicy::Penalize, ResearchSkill::None)
+    }
+
+    fn assert_same(a: &CostTable, b: &CostTable) {
+        assert_eq!(a.aspects(), b.aspects());
+        assert_eq!(a.entries, b.entries);
+    }
+
+    #[test]
+    fn tables_survive_encoding() {
+        let solver = default_solver();
+        let cost_table = CostTable::build(&solver);
+        let decoded = CostTable::decode(Edition::Tc4.aspects(), &cost_table.encode()).unwrap();
+        assert_same(&cost_table, &decoded);
+
+        let path_table = solver.find_paths(Aspect::Aer, 4);
+        assert_eq!(decoded.price(Aspect::Aer, Aspect::Ignis, 4), path_table.price(Aspect::Ignis, 4));
+        assert_eq!(decoded.count(Aspect::Aer, Aspect::Ignis, 4), path_table.count(Aspect::Ignis, 4));
+        assert_eq!(decoded.price(Aspect::Aer, Aspect::Vitreus, 3), None);
+        assert_eq!(decoded.price(Aspect::Aer, Aspect::Ignis, CostTable::MAX_LENGTH + 1), None);
+    }
+
+    #[test]
+    fn keys_change_with_everything_prices_depend_on() {
+        let key = CostTable::inventory_key(&default_solver());
+        assert_eq!(key, CostTable::inventory_key(&default_solver()));
+
+        let others = [
+            solver(&[(Aspect::Lux, 8)], &[], ReservePolicy::Penalize, ResearchSkill::None),
+            solver(&[(Aspect::Lux, 9), (Aspect::Aer, 0)], &[], ReservePolicy::Penalize, ResearchSkill::None),
+            solver(&[(Aspect::Lux, 9), (Aspect::Vitreus, 0)], &[], ReservePolicy::Penalize, ResearchSkill::None),
+            solver(&[(Aspect::Lux, 9)], &[(Aspect::Aer, 3)], ReservePolicy::Penalize, ResearchSkill::None),
+            solver(&[(Aspect::Lux, 9)], &[], ReservePolicy::Forbid, ResearchSkill::None),
+            solver(&[(Aspect::Lux, 9)], &[], ReservePolicy::Penalize, ResearchSkill::Mastery),
+        ];
+        for other in &others {
+            assert_ne!(CostTable::inventory_key(other), key);
+        }
+
+        let default_solver = default_solver();
+        let rescaled = Solver::new(
+            default_solver.aspect_inventory().with_max_amount(20),
+            default_solver.reserves().clone(),
+            default_solver.research_skill(),
+        );
+        assert_ne!(CostTable::inventory_key(&rescaled), key);
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let content = CostTable::build(&default_solver()).encode();
+        let aspects = Edition::Tc4.aspects();
+        assert!(CostTable::decode(aspects.clone(), &content[..content.len() - 1]).is_none());
+        assert!(CostTable::decode(aspects.clone(), &content[..5]).is_none());
+        assert!(CostTable::decode(aspects.clone(), &[]).is_none());
+
+        let mut other_version = content.clone();
+        other_version[4] = CostTable::VERSION + 1;
+        assert!(CostTable::decode(aspects.clone(), &other_version).is_none());
+        let mut other_magic = content.clone();
+        other_magic[0] = b'X';
+        assert!(CostTable::decode(aspects.clone(), &other_magic).is_none());
+        assert!(CostTable::decode(Edition::Tc6.aspects(), &content).is_none());
+    }
+
+    #[test]
+    fn damaged_cache_files_are_rebuilt() {
+        let dir = test_dir("rebuild");
+        let solver = default_solver();
+        let built = CostTable::load_or_build(&solver, &dir);
+        let path = dir.join(format!("{:016x}.{}", CostTable::inventory_key(&solver), CostTable::EXTENSION));
+        let content = fs::read(&path).unwrap();
+        assert_same(&built, &CostTable::decode(Edition::Tc4.aspects(), &content).unwrap());
+
+        fs::write(&path, &content[..100]).unwrap();
+        assert_same(&built, &CostTable::load_or_build(&solver, &dir));
+        assert_eq!(fs::read(&path).unwrap(), content);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
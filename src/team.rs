@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use crate::{
+    aspect::{Aspect, AspectInventory},
+    solver::Solver,
+};
+
+pub struct TeamMember {
+    pub name: String,
+    /// Priced against the pooled inventory, so that the prices of all members compare
+    pub solver: Solver,
+}
+
+pub struct Team {
+    members: Vec<TeamMember>,
+}
+
+impl Team {
+    /// Moves every member onto the price scale of the pooled inventory and precomputes their cost tables,
+    /// cached in `cache_dir` when given.
+    pub fn new(mut members: Vec<TeamMember>, cache_dir: Option<&Path>) -> Self {
+        let max_amount = AspectInventory::pooled(members.iter().map(|member| member.solver.aspect_inventory())).max_amount();
+        for member in &mut members {
+            let aspect_inventory = member.solver.aspect_inventory().with_max_amount(max_amount);
+            member.solver = Solver::new(aspect_inventory, member.solver.reserves().clone(), member.solver.research_skill());
+            member.solver.precompute_costs(cache_dir);
+        }
+
+        Team { members }
+    }
+
+    pub fn pooled_inventory(&self) -> AspectInventory {
+        AspectInventory::pooled(self.members.iter().map(|member| member.solver.aspect_inventory()))
+    }
+
+    /// Replaces the inventory of the named member, if they are part of the team. When the pooled maximum changes,
+    /// every member moves to the new price scale.
+    pub fn set_aspect_inventory(&mut self, name: &str, aspect_inventory: AspectInventory) {
+        let Some(index) = self.members.iter().position(|member| member.name == name) else {
+            return;
+        };
+        let inventories = self
+            .members
+            .iter()
+            .enumerate()
+            .map(|(other, member)| if other == index { &aspect_inventory } else { member.solver.aspect_inventory() });
+        let max_amount = AspectInventory::pooled(inventories).max_amount();
+        let rescale_all = max_amount != self.pooled_inventory().max_amount();
+
+        for (other, member) in self.members.iter_mut().enumerate() {
+            if other == index {
+                member.solver.set_aspect_inventory(aspect_inventory.with_max_amount(max_amount));
+            } else if rescale_all {
+                member.solver.set_aspect_inventory(member.solver.aspect_inventory().with_max_amount(max_amount));
+            }
+        }
+    }
+
+    pub fn print_inventory_table(&self) {
+        let pooled_inventory = self.pooled_inventory();
+        let aspects = pooled_inventory.edition().aspects();
+        let name_width = aspects.iter().map(|aspect| aspect.display_name().len()).max().unwrap_or_default();
+        let column_widths: Vec<usize> = self.members.iter().map(|member| member.name.len().max(5)).collect();
+
+        print!("{:<name_width$}", "aspect");
+        for (member, width) in self.members.iter().zip(&column_widths) {
+            print!("  {:>width$}", member.name);
+        }
+        println!("  {:>5}", "total");
+
+        for aspect in aspects {
+            if !pooled_inventory.is_discovered(aspect) {
+                continue;
+            }
+
+            print!("{:<name_width$}", aspect.display_name());
+            for (member, width) in self.members.iter().zip(&column_widths) {
+                print!("  {:>width$}", member.solver.aspect_inventory().display_amount(aspect));
+            }
+            println!("  {:>5}", pooled_inventory.amount_of(aspect));
+        }
+        println!("'-' marks aspects the player has not discovered yet.");
+    }
+
+    /// Ranks team members by the cheapest path they can build between the two aspects,
+    /// returning the member together with the path length and its price on the pooled scale.
+    pub fn rank_by_price(&self, start: Aspect, end: Aspect, distance: u8, max_distance_increase: u8) -> Vec<(&TeamMember, u8, u32)> {
+        let mut ranking: Vec<(&TeamMember, u8, u32)> = self
+            .members
+            .iter()
+            .filter_map(|member| {
+                member
+                    .solver
+                    .find_cheapest_price(start, end, distance, max_distance_increase)
+                    .map(|(length, price)| (member, length, price))
+            })
+            .collect();
+        ranking.sort_by_key(|&(_, length, price)| (price, length));
+
+        ranking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{aspect::Edition, reserve::Reserves, skill::ResearchSkill};
+
+    fn inventory(amounts: &[(Aspect, u16)]) -> AspectInventory {
+        AspectInventory::new(amounts.iter().copied().collect::<HashMap<_, _>>(), Edition::Tc4)
+    }
+
+    fn member(name: &str, amounts: &[(Aspect, u16)]) -> TeamMember {
+        TeamMember {
+            name: name.to_string(),
+            solver: Solver::new(inventory(amounts), Reserves::default(), ResearchSkill::None),
+        }
+    }
+
+    /// Lux is the only aspect linking Aer and Ignis, so the member holding more of it connects them cheaper.
+    fn team() -> Team {
+        Team::new(
+            vec![
+                member("bob", &[(Aspect::Aer, 2), (Aspect::Lux, 30), (Aspect::Ignis, 5)]),
+                member("alice", &[(Aspect::Aer, 10), (Aspect::Lux, 40)]),
+                member("carol", &[(Aspect::Aer, 50), (Aspect::Ignis, 1)]),
+            ],
+            None,
+        )
+    }
+
+    #[test]
+    fn members_are_priced_against_the_pooled_maximum() {
+        let team = team();
+        assert_eq!(team.pooled_inventory().max_amount(), 70);
+        for member in &team.members {
+            assert_eq!(member.solver.aspect_inventory().max_amount(), 70, "{}", member.name);
+            assert!(member.solver.cost_table().is_some(), "{}", member.name);
+        }
+        assert_eq!(team.members[0].solver.step_price(Aspect::Lux), Some((70 + 1 - 30) * Solver::PRICE_SCALE));
+    }
+
+    #[test]
+    fn ranking_puts_the_cheapest_member_first() {
+        let team = team();
+        let ranking: Vec<(&str, u8, u32)> = team
+            .rank_by_price(Aspect::Aer, Aspect::Ignis, 3, 1)
+            .into_iter()
+            .map(|(member, length, price)| (member.name.as_str(), length, price))
+            .collect();
+        // Both own prices would be the lowest one, as each of them holds the most Lux of their own inventory
+        assert_eq!(ranking, vec![("alice", 3, 31 * Solver::PRICE_SCALE), ("bob", 3, 41 * Solver::PRICE_SCALE)]);
+    }
+
+    #[test]
+    fn inventory_updates_move_everyone_to_the_new_scale() {
+        let mut team = team();
+        team.set_aspect_inventory("carol", inventory(&[(Aspect::Aer, 20), (Aspect::Ignis, 1)]));
+        assert_eq!(team.pooled_inventory().max_amount(), 70);
+        assert_eq!(team.members[1].solver.aspect_inventory().max_amount(), 70);
+
+        team.set_aspect_inventory("bob", inventory(&[(Aspect::Aer, 2), (Aspect::Lux, 10)]));
+        assert_eq!(team.pooled_inventory().max_amount(), 50);
+        for member in &team.members {
+            assert_eq!(member.solver.aspect_inventory().max_amount(), 50, "{}", member.name);
+        }
+        let ranking = team.rank_by_price(Aspect::Aer, Aspect::Ignis, 3, 1);
+        assert_eq!(ranking[0].2, (50 + 1 - 40) * Solver::PRICE_SCALE);
+        assert_eq!(
+            team.members[0].solver.cost_table().unwrap().price(Aspect::Aer, Aspect::Ignis, 3),
+            Some((50 + 1 - 10) * Solver::PRICE_SCALE)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::{aspect::AspectInventory, player::PlayerData, source::InventorySource};
+
+struct WatchedPlayer {
+    name: String,
+    modified: Option<SystemTime>,
+    aspect_inventory: AspectInventory,
+    last_error: Option<String>,
+}
+
+/// Polls the source for changed `.thaum` files, only downloading them again when the source
+/// reports a new modification time or cannot tell at all.
+pub struct Watcher {
+    source: Box<dyn InventorySource>,
+    players: Vec<WatchedPlayer>,
+    interval: Duration,
+}
+
+impl Watcher {
+    pub fn new(mut source: Box<dyn InventorySource>, players: &[PlayerData], interval: Duration) -> Self {
+        let players = players
+            .iter()
+            .map(|player| WatchedPlayer {
+                modified: source.modified(&player.name).ok().flatten(),
+                name: player.name.clone(),
+                aspect_inventory: player.aspect_inventory.clone(),
+                last_error: None,
+            })
+            .collect();
+
+        Watcher { source, players, interval }
+    }
+
+    /// Polls forever in a background thread, calling `on_change` with every refreshed inventory.
+    pub fn spawn(mut self, mut on_change: impl FnMut(&str, AspectInventory) + Send + 'static) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            thread::sleep(self.interval);
+            self.poll(&mut on_change);
+        })
+    }
+
+    fn poll(&mut self, on_change: &mut impl FnMut(&str, AspectInventory)) {
+        for index in 0..self.players.len() {
+            let result = self.refresh(index);
+            let player = &mut self.players[index];
+            match result {
+                Ok(Some(aspect_inventory)) => {
+                    let changes = Watcher::format_changes(&player.aspect_inventory, &aspect_inventory);
+                    if !changes.is_empty() {
+                        println!("\nInventory of {} changed: {}", player.name, changes.join(", "));
+                        on_change(&player.name, aspect_inventory.clone());
+                    }
+                    player.aspect_inventory = aspect_inventory;
+                    player.last_error = None;
+                }
+                Ok(None) => player.last_error = None,
+                Err(error) => {
+                    if player.last_error.as_ref() != Some(&error) {
+                        println!("\nWarning: could not refresh the inventory of {}: {}", player.name, error);
+                    }
+                    player.last_error = Some(error);
+                }
+            }
+        }
+    }
+
+    /// Downloads the inventory again unless the file is known to be unchanged.
+    fn refresh(&mut self, index: usize) -> Result<Option<AspectInventory>, String> {
+        let player = &self.players[index];
+        let modified = self.source.modified(&player.name)?;
+        if modified.is_some() && modified == player.modified {
+            return Ok(None);
+        }
+
+        let content = self.source.read_thaum(&player.name)?;
+        let player_data = PlayerData::from_thaum(player.name.clone(), &content)?;
+        self.players[index].modified = modified;
+
+        Ok(Some(player_data.aspect_inventory))
+    }
+
+    /// Describes every aspect whose amount or discovery changed, e.g. `aer 17 -> 15 (-2)`.
+    fn format_changes(old: &AspectInventory, new: &AspectInventory) -> Vec<String> {
+        let mut changes = Vec::new();
+        for aspect in new.edition().aspects() {
+            match (old.is_discovered(aspect), new.is_discovered(aspect)) {
+                (false, true) => changes.push(format!("{} discovered ({})", aspect.display_name(), new.amount_of(aspect))),
+                (true, false) => changes.push(format!("{} forgotten", aspect.display_name())),
+                (true, true) if old.amount_of(aspect) != new.amount_of(aspect) => {
+                    let (old_amount, new_amount) = (old.amount_of(aspect), new.amount_of(aspect));
+                    changes.push(format!(
+                        "{} {} -> {} ({:+})",
+                        aspect.display_name(),
+                        old_amount,
+                        new_amount,
+                        new_amount as i32 - old_amount as i32
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aspect::{Aspect, Edition},
+        source::{MemorySource, SourceError},
+    };
+
+    fn inventory(amounts: &[(Aspect, u16)]) -> AspectInventory {
+        AspectInventory::new(amounts.iter().copied().collect(), Edition::Tc4)
+    }
+
+    #[test]
+    fn format_changes_describes_amounts_and_discoveries() {
+        let old = inventory(&[(Aspect::Aer, 17), (Aspect::Ignis, 3), (Aspect::Ordo, 1)]);
+        let new = inventory(&[(Aspect::Aer, 15), (Aspect::Ignis, 3), (Aspect::Terra, 4), (Aspect::Lux, 0)]);
+
+        let changes = Watcher::format_changes(&old, &new);
+        assert_eq!(changes, ["aer 17 -> 15 (-2)", "lux discovered (0)", "ordo forgotten", "terra discovered (4)"]);
+        assert!(Watcher::format_changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn poll_reports_changes_and_keeps_going_after_errors() {
+        let server = MemorySource::default();
+        server.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 12)], &[]));
+        let alice = PlayerData::from_thaum("alice".to_string(), &MemorySource::thaum_file(&[(Aspect::Aer, 12)], &[])).unwrap();
+        let mut watcher = Watcher::new(Box::new(server.clone()), &[alice], Duration::from_secs(1));
+
+        let mut changed = Vec::new();
+        let mut on_change = |name: &str, aspect_inventory: AspectInventory| changed.push((name.to_string(), aspect_inventory.amount_of(Aspect::Aer)));
+        watcher.poll(&mut on_change);
+
+        server.set_thaum("alice", MemorySource::thaum_file(&[(Aspect::Aer, 9)], &[]));
+        let error = SourceError::Network("timed out".to_string());
+        server.fail_next(error.clone());
+        watcher.poll(&mut on_change);
+        assert_eq!(watcher.players[0].last_error, Some(error.to_string()));
+
+        watcher.poll(&mut on_change);
+        watcher.poll(&mut on_change);
+        assert!(watcher.players[0].last_error.is_none());
+        assert_eq!(changed, [("alice".to_string(), 9)]);
+    }
+}